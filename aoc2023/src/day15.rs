@@ -15,44 +15,181 @@ pub fn part1(input: &str) -> u32 {
 #[must_use]
 pub fn part2(input: &str) -> usize {
     let input = parse(input);
-    let mut boxes = vec![smallvec::SmallVec::<[_; 5]>::new(); 256];
+    let mut boxes = utils::LensBoxes::new();
     for step in &input {
-        let (label, focal_length) = step.split_once(['=', '-']).unwrap();
-        let current_box = boxes.get_mut(utils::hash(label) as usize).unwrap();
-        let slot_index = current_box.iter().position(|&(l, _)| l == label);
-        if step.contains('=') {
-            match slot_index {
-                Some(i) => {
-                    current_box[i] = (label, focal_length);
+        boxes.apply(&step.parse().unwrap());
+    }
+    boxes.focusing_power()
+}
+
+pub mod utils {
+    use std::fmt::Write;
+
+    pub fn hash(input: &str) -> u8 {
+        input
+            .bytes()
+            .fold(0, |acc, byte| acc.wrapping_add(byte).wrapping_mul(17))
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Operation {
+        /// Puts the lens into the box of the label, replacing a lens with the same label.
+        Insert { label: String, focal_length: u8 },
+        /// Removes the lens with the label from its box.
+        Remove { label: String },
+    }
+
+    impl std::str::FromStr for Operation {
+        type Err = &'static str;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            if let Some(label) = s.strip_suffix('-') {
+                Ok(Self::Remove {
+                    label: label.to_owned(),
+                })
+            } else {
+                let (label, focal_length) = s.split_once('=').ok_or("Invalid operation")?;
+                Ok(Self::Insert {
+                    label: label.to_owned(),
+                    focal_length: focal_length.parse().map_err(|_| "Invalid focal length")?,
+                })
+            }
+        }
+    }
+
+    impl std::fmt::Display for Operation {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Insert {
+                    label,
+                    focal_length,
+                } => write!(f, "{label}={focal_length}"),
+                Self::Remove { label } => write!(f, "{label}-"),
+            }
+        }
+    }
+
+    /// Hash map whose buckets keep their lenses in insertion order.
+    #[derive(Clone)]
+    pub struct LensBoxes<H = fn(&str) -> usize> {
+        boxes: Vec<smallvec::SmallVec<[(String, u8); 5]>>,
+        hash: H,
+    }
+
+    impl LensBoxes {
+        /// The 256 boxes of the HASHMAP procedure.
+        #[must_use]
+        pub fn new() -> Self {
+            Self::with_hasher(256, |label| usize::from(hash(label)))
+        }
+    }
+
+    impl Default for LensBoxes {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl<H: Fn(&str) -> usize> LensBoxes<H> {
+        /// Boxes that assign each label to box `hash(label) % n_boxes`.
+        ///
+        /// # Panics
+        /// Panics if there are no boxes.
+        pub fn with_hasher(n_boxes: usize, hash: H) -> Self {
+            assert!(n_boxes > 0, "There must be at least one box");
+            Self {
+                boxes: vec![smallvec::SmallVec::new(); n_boxes],
+                hash,
+            }
+        }
+
+        fn box_index(&self, label: &str) -> usize {
+            (self.hash)(label) % self.boxes.len()
+        }
+
+        pub fn apply(&mut self, operation: &Operation) {
+            match operation {
+                Operation::Insert {
+                    label,
+                    focal_length,
+                } => {
+                    let i = self.box_index(label);
+                    match self.boxes[i].iter_mut().find(|(l, _)| l == label) {
+                        Some((_, f)) => *f = *focal_length,
+                        None => self.boxes[i].push((label.clone(), *focal_length)),
+                    }
                 }
-                None => {
-                    current_box.push((label, focal_length));
+                Operation::Remove { label } => {
+                    let i = self.box_index(label);
+                    self.boxes[i].retain(|(l, _)| l != label);
                 }
             }
-        } else if let Some(i) = slot_index {
-            current_box.remove(i);
         }
-    }
 
-    boxes
-        .iter()
-        .enumerate()
-        .flat_map(|(box_index, slot)| {
-            slot.iter()
+        /// Applies the operations one by one, yielding the state after each operation in the
+        /// format of the puzzle's walkthrough.
+        pub fn replay<'a>(
+            &'a mut self,
+            operations: impl IntoIterator<Item = Operation> + 'a,
+        ) -> impl Iterator<Item = String> + 'a {
+            operations.into_iter().map(move |operation| {
+                self.apply(&operation);
+                format!("After \"{operation}\":\n{self}")
+            })
+        }
+
+        /// Box and slot of the lens with the label.
+        #[must_use]
+        pub fn position(&self, label: &str) -> Option<(usize, usize)> {
+            let i = self.box_index(label);
+            self.boxes[i]
+                .iter()
+                .position(|(l, _)| l == label)
+                .map(|slot| (i, slot))
+        }
+
+        /// Focal length of the lens with the label.
+        #[must_use]
+        pub fn get(&self, label: &str) -> Option<u8> {
+            self.position(label).map(|(i, slot)| self.boxes[i][slot].1)
+        }
+
+        /// Lenses of the box as `(label, focal_length)` pairs.
+        #[must_use]
+        pub fn lenses(&self, i: usize) -> &[(String, u8)] {
+            &self.boxes[i]
+        }
+
+        #[must_use]
+        pub fn focusing_power(&self) -> usize {
+            self.boxes
+                .iter()
                 .enumerate()
-                .map(move |(slot_index, &(_, focal_length))| {
-                    let focal_length: usize = focal_length.parse().unwrap();
-                    (box_index + 1) * (slot_index + 1) * focal_length
+                .flat_map(|(box_index, lenses)| {
+                    lenses
+                        .iter()
+                        .enumerate()
+                        .map(move |(slot_index, &(_, focal_length))| {
+                            (box_index + 1) * (slot_index + 1) * usize::from(focal_length)
+                        })
                 })
-        })
-        .sum()
-}
+                .sum()
+        }
+    }
 
-mod utils {
-    pub fn hash(input: &str) -> u8 {
-        input
-            .bytes()
-            .fold(0, |acc, byte| acc.wrapping_add(byte).wrapping_mul(17))
+    /// Lists the non-empty boxes.
+    impl<H> std::fmt::Display for LensBoxes<H> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            for (i, lenses) in self.boxes.iter().enumerate() {
+                if !lenses.is_empty() {
+                    let mut line = format!("Box {i}:");
+                    for (label, focal_length) in lenses {
+                        write!(line, " [{label} {focal_length}]")?;
+                    }
+                    writeln!(f, "{line}")?;
+                }
+            }
+            Ok(())
+        }
     }
 }
 
@@ -74,4 +211,48 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 145);
     }
+
+    #[test]
+    pub fn replay_example() {
+        let mut boxes = utils::LensBoxes::new();
+        let operations = parse(SAMPLE)
+            .iter()
+            .map(|step| step.parse().unwrap())
+            .collect::<Vec<_>>();
+        let states = boxes.replay(operations).collect::<Vec<_>>();
+        assert_eq!(
+            states[3],
+            indoc! {r#"
+                After "cm=2":
+                Box 0: [rn 1] [cm 2]
+                Box 1: [qp 3]
+            "#}
+        );
+        assert_eq!(
+            states.last().unwrap(),
+            indoc! {r#"
+                After "ot=7":
+                Box 0: [rn 1] [cm 2]
+                Box 3: [ot 7] [ab 5] [pc 6]
+            "#}
+        );
+        assert_eq!(boxes.get("ot"), Some(7));
+        assert_eq!(boxes.position("pc"), Some((3, 2)));
+        assert_eq!(boxes.get("qp"), None);
+        assert_eq!(boxes.focusing_power(), 145);
+    }
+
+    #[test]
+    pub fn custom_hasher() {
+        let mut boxes = utils::LensBoxes::with_hasher(4, str::len);
+        for step in parse(SAMPLE) {
+            boxes.apply(&step.parse().unwrap());
+        }
+        assert_eq!(boxes.lenses(2).len(), 5);
+        assert_eq!(boxes.position("ab"), Some((2, 3)));
+        assert_eq!(
+            boxes.to_string(),
+            "Box 2: [rn 1] [cm 2] [ot 7] [ab 5] [pc 6]\n"
+        );
+    }
 }
@@ -25,9 +25,8 @@ pub fn part1(input: &str) -> usize {
 #[aoc(day16, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    use rayon::prelude::*;
-
     let input = parse(input);
+    let tracer = utils::BeamTracer::new(&input);
     (0..input.columns)
         .map(|x| utils::Beam::new((x, 0), utils::Direction::South))
         .chain(
@@ -39,13 +38,14 @@ pub fn part2(input: &str) -> usize {
             (0..input.rows)
                 .map(|y| utils::Beam::new((input.columns - 1, y), utils::Direction::West)),
         )
-        .par_bridge()
-        .map(|beam| input.get_energized_tiles(beam).len())
+        .map(|beam| tracer.energized_count(beam))
         .max()
         .unwrap()
 }
 
-mod utils {
+pub mod utils {
+    use itertools::Itertools;
+
     #[derive(derive_more::Deref)]
     pub struct Cave(pathfinding::matrix::Matrix<Tile>);
 
@@ -71,6 +71,194 @@ mod utils {
             }
             beams
         }
+
+        /// Renders the tiles that the beam energizes as `#`.
+        #[must_use]
+        pub fn render_energized(&self, beam: Beam) -> String {
+            let energized = self.get_energized_tiles(beam);
+            self.render(|position| {
+                if energized.contains(&position) {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+        }
+
+        /// Renders the directions of the beams on empty tiles, using the number of beams if
+        /// multiple beams pass through the same tile.
+        #[must_use]
+        pub fn render_beams(&self, beam: Beam) -> String {
+            let beams = self.beamform(beam);
+            self.render(|position| {
+                let directions = [
+                    Direction::North,
+                    Direction::West,
+                    Direction::South,
+                    Direction::East,
+                ]
+                .into_iter()
+                .filter(|&direction| beams.contains(&Beam::new(position, direction)))
+                .collect::<smallvec::SmallVec<[_; 4]>>();
+                match (self[position], directions.as_slice()) {
+                    (Tile::Empty, []) => '.',
+                    (Tile::Empty, [direction]) => direction.symbol(),
+                    (Tile::Empty, directions) => char::from(b'0' + directions.len() as u8),
+                    (tile, _) => char::from(tile as u8),
+                }
+            })
+        }
+
+        fn render(&self, symbol: impl Fn((usize, usize)) -> char) -> String {
+            (0..self.rows)
+                .map(|y| {
+                    (0..self.columns)
+                        .map(|x| symbol((x, y)))
+                        .collect::<String>()
+                        + "\n"
+                })
+                .collect()
+        }
+    }
+
+    /// Precomputed tiles energized by every possible beam.
+    ///
+    /// Beams are nodes of a graph whose edges follow the propagation of beams. Its strongly
+    /// connected components are condensed into a DAG, where each component energizes its own
+    /// tiles and everything downstream. Downstream sets are only stored for components that
+    /// branch or contain cycles, while simple chains of single beams refer to the next
+    /// component, so that entry beams share the sets of the components they run into.
+    ///
+    /// Building the tracer costs more than tracing a single beam with
+    /// [`Cave::get_energized_tiles`], so it only pays off when many entry beams are queried.
+    pub struct BeamTracer<'a> {
+        cave: &'a Cave,
+        /// Component of each beam, indexed by `(y * columns + x) * 4 + direction`.
+        components: Vec<usize>,
+        downstream: Vec<Downstream>,
+        /// Bit sets of the energized tiles with their number of tiles.
+        sets: Vec<(Vec<u64>, usize)>,
+    }
+
+    enum Downstream {
+        Chain { tile: usize, next: usize },
+        Set(usize),
+    }
+
+    impl<'a> BeamTracer<'a> {
+        #[must_use]
+        pub fn new(cave: &'a Cave) -> Self {
+            let n_tiles = cave.rows * cave.columns;
+            let mut graph = petgraph::Graph::<(), ()>::with_capacity(4 * n_tiles, 4 * n_tiles);
+            (0..4 * n_tiles).for_each(|_| {
+                graph.add_node(());
+            });
+            for y in 0..cave.rows {
+                for x in 0..cave.columns {
+                    for direction in [
+                        Direction::North,
+                        Direction::West,
+                        Direction::South,
+                        Direction::East,
+                    ] {
+                        let beam = Beam::new((x, y), direction);
+                        for next in beam.propagate(cave) {
+                            graph.add_edge(
+                                petgraph::graph::NodeIndex::new(beam.index(cave)),
+                                petgraph::graph::NodeIndex::new(next.index(cave)),
+                                (),
+                            );
+                        }
+                    }
+                }
+            }
+
+            // Components are in reverse topological order, i.e. successors come first
+            let sccs = petgraph::algo::tarjan_scc(&graph);
+            let mut components = vec![0; graph.node_count()];
+            for (component, scc) in sccs.iter().enumerate() {
+                scc.iter()
+                    .for_each(|node| components[node.index()] = component);
+            }
+
+            let mut tracer = Self {
+                cave,
+                components,
+                downstream: Vec::with_capacity(sccs.len()),
+                sets: Vec::new(),
+            };
+            for (component, scc) in sccs.iter().enumerate() {
+                let successors = scc
+                    .iter()
+                    .flat_map(|&node| graph.neighbors(node))
+                    .map(|node| tracer.components[node.index()])
+                    .filter(|&next| next != component)
+                    .unique()
+                    .collect::<smallvec::SmallVec<[_; 4]>>();
+                if let (&[node], &[next]) = (scc.as_slice(), successors.as_slice()) {
+                    tracer.downstream.push(Downstream::Chain {
+                        tile: node.index() / 4,
+                        next,
+                    });
+                    continue;
+                }
+
+                let mut set = vec![0; n_tiles.div_ceil(64)];
+                let mut tiles = scc.iter().map(|node| node.index() / 4).collect_vec();
+                for next in successors {
+                    let (chain, next_set) = tracer.resolve(next);
+                    tiles.extend(chain);
+                    set.iter_mut()
+                        .zip(&tracer.sets[next_set].0)
+                        .for_each(|(a, b)| *a |= b);
+                }
+                tiles
+                    .into_iter()
+                    .for_each(|tile| set[tile / 64] |= 1 << (tile % 64));
+                let n_energized = set.iter().map(|x| x.count_ones() as usize).sum();
+                tracer.downstream.push(Downstream::Set(tracer.sets.len()));
+                tracer.sets.push((set, n_energized));
+            }
+            tracer
+        }
+
+        /// Follows the chain of single beams from the component to the next stored set.
+        fn resolve(&self, mut component: usize) -> (Vec<usize>, usize) {
+            let mut tiles = Vec::new();
+            loop {
+                match self.downstream[component] {
+                    Downstream::Chain { tile, next } => {
+                        tiles.push(tile);
+                        component = next;
+                    }
+                    Downstream::Set(set) => return (tiles, set),
+                }
+            }
+        }
+
+        #[must_use]
+        pub fn energized_count(&self, beam: Beam) -> usize {
+            let (mut tiles, set) = self.resolve(self.components[beam.index(self.cave)]);
+            let (set, n_energized) = &self.sets[set];
+            tiles.sort_unstable();
+            tiles.dedup();
+            n_energized
+                + tiles
+                    .iter()
+                    .filter(|&&tile| set[tile / 64] & (1 << (tile % 64)) == 0)
+                    .count()
+        }
+
+        #[must_use]
+        pub fn energized_tiles(&self, beam: Beam) -> rustc_hash::FxHashSet<(usize, usize)> {
+            let (tiles, set) = self.resolve(self.components[beam.index(self.cave)]);
+            let (set, _) = &self.sets[set];
+            (0..self.cave.rows * self.cave.columns)
+                .filter(|&tile| set[tile / 64] & (1 << (tile % 64)) != 0)
+                .chain(tiles)
+                .map(|tile| (tile % self.cave.columns, tile / self.cave.columns))
+                .collect()
+        }
     }
 
     #[repr(u8)]
@@ -104,6 +292,17 @@ mod utils {
         East,
     }
 
+    impl Direction {
+        fn symbol(self) -> char {
+            match self {
+                Self::North => '^',
+                Self::West => '<',
+                Self::South => 'v',
+                Self::East => '>',
+            }
+        }
+    }
+
     #[derive(Clone, Copy, PartialEq, Eq, Hash)]
     pub struct Beam {
         position: (usize, usize),
@@ -118,6 +317,10 @@ mod utils {
             }
         }
 
+        fn index(&self, cave: &Cave) -> usize {
+            (self.position.1 * cave.columns + self.position.0) * 4 + self.direction as usize
+        }
+
         fn propagate(&self, cave: &Cave) -> Vec<Self> {
             match (self.direction, cave[self.position]) {
                 (Direction::West, Tile::MirrorDiagonal)
@@ -192,4 +395,57 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 51);
     }
+
+    #[test]
+    pub fn tracer_example() {
+        let cave = parse(SAMPLE);
+        let tracer = utils::BeamTracer::new(&cave);
+        for (position, direction) in [
+            ((0, 0), utils::Direction::East),
+            ((3, 0), utils::Direction::South),
+            ((9, 4), utils::Direction::West),
+            ((5, 9), utils::Direction::North),
+        ] {
+            let beam = utils::Beam::new(position, direction);
+            let energized = cave.get_energized_tiles(beam);
+            assert_eq!(tracer.energized_tiles(beam), energized);
+            assert_eq!(tracer.energized_count(beam), energized.len());
+        }
+    }
+
+    #[test]
+    pub fn render_example() {
+        let cave = parse(SAMPLE);
+        let beam = utils::Beam::new((0, 0), utils::Direction::East);
+        assert_eq!(
+            cave.render_energized(beam),
+            indoc! {"
+                ######....
+                .#...#....
+                .#...#####
+                .#...##...
+                .#...##...
+                .#...##...
+                .#..####..
+                ########..
+                .#######..
+                .#...#.#..
+            "}
+        );
+        assert_eq!(
+            cave.render_beams(beam),
+            indoc! {r"
+                >|<<<\....
+                |v-.\^....
+                .v...|->>>
+                .v...v^.|.
+                .v...v^...
+                .v...v^..\
+                .v../2\\..
+                <->-/vv|..
+                .|<<<2-|.\
+                .v//.|.v..
+            "}
+        );
+    }
 }
@@ -1,17 +1,7 @@
 use aoc_runner_derive::aoc;
 
 fn parse(input: &str) -> utils::Map {
-    let mut matrix = pathfinding::matrix::Matrix::new(
-        input.lines().count(),
-        input.lines().next().unwrap().len(),
-        0,
-    );
-    input.lines().enumerate().for_each(|(y, line)| {
-        line.chars().enumerate().for_each(|(x, c)| {
-            matrix[(x, y)] = c.to_digit(10).unwrap();
-        });
-    });
-    utils::Map::new(matrix)
+    utils::Map::parse(input)
 }
 
 #[aoc(day17, part1)]
@@ -28,146 +18,187 @@ pub fn part2(input: &str) -> u32 {
     input.find_shortest_path(4, 10)
 }
 
-mod utils {
-    #[derive(derive_more::Deref)]
-    pub struct Map(pathfinding::matrix::Matrix<u32>);
+pub mod utils {
+    use itertools::Itertools;
 
-    impl Map {
-        pub fn new(value: pathfinding::matrix::Matrix<u32>) -> Self {
-            Self(value)
-        }
-
-        pub fn find_shortest_path(
-            &self,
-            min_consecutive_moves: usize,
-            max_consecutive_moves: usize,
-        ) -> u32 {
-            let start_nodes = [
-                Node::new((0, 0), Direction::South, 0),
-                Node::new((0, 0), Direction::East, 0),
-            ];
-
-            start_nodes
-                .iter()
-                .map(|start| {
-                    let (_, distance) = pathfinding::prelude::dijkstra(
-                        start,
-                        |node| node.successors(self, min_consecutive_moves, max_consecutive_moves),
-                        |node| node.success(self, min_consecutive_moves),
-                    )
-                    .unwrap();
-                    distance
-                })
-                .min()
-                .unwrap()
-        }
-
-        fn propagate(
-            &self,
-            position: (usize, usize),
-            direction: Direction,
-        ) -> Option<(usize, usize)> {
-            match direction {
-                Direction::North => position.1.checked_sub(1).map(|next_y| (position.0, next_y)),
-                Direction::West => position.0.checked_sub(1).map(|next_x| (next_x, position.1)),
-                Direction::South => {
-                    let next_y = position.1 + 1;
-                    (next_y < self.rows).then_some((position.0, next_y))
-                }
-                Direction::East => {
-                    let next_x = position.0 + 1;
-                    (next_x < self.columns).then_some((next_x, position.1))
-                }
-            }
-        }
+    /// Heat loss of each city block, indexed by `y * width + x`.
+    pub struct Map {
+        width: usize,
+        height: usize,
+        heat_loss: Vec<u8>,
     }
 
-    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
-    enum Direction {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum Direction {
         North,
         West,
         South,
         East,
     }
 
-    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
-    struct Node {
-        position: (usize, usize),
-        direction: Direction,
-        n_consecutive_moves: usize,
+    impl Direction {
+        fn symbol(self) -> char {
+            match self {
+                Self::North => '^',
+                Self::West => '<',
+                Self::South => 'v',
+                Self::East => '>',
+            }
+        }
+    }
+
+    /// Straight run of `steps` moves in the same direction.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Move {
+        pub direction: Direction,
+        pub steps: usize,
     }
 
-    impl Node {
-        fn new(position: (usize, usize), direction: Direction, n_consecutive_moves: usize) -> Self {
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Route {
+        pub heat_loss: u32,
+        pub moves: Vec<Move>,
+    }
+
+    impl Route {
+        /// Direction of each single step along the route.
+        pub fn directions(&self) -> impl Iterator<Item = Direction> + '_ {
+            self.moves
+                .iter()
+                .flat_map(|m| std::iter::repeat(m.direction).take(m.steps))
+        }
+    }
+
+    impl Map {
+        pub fn parse(input: &str) -> Self {
+            let heat_loss = input
+                .lines()
+                .flat_map(|line| line.bytes().map(|b| b - b'0'))
+                .collect_vec();
+            let width = input.lines().next().unwrap().len();
             Self {
-                position,
-                direction,
-                n_consecutive_moves,
+                width,
+                height: heat_loss.len() / width,
+                heat_loss,
             }
         }
 
-        fn available_directions(
+        pub fn find_shortest_path(
             &self,
             min_consecutive_moves: usize,
             max_consecutive_moves: usize,
-        ) -> Vec<Direction> {
-            if self.n_consecutive_moves < min_consecutive_moves {
-                vec![self.direction]
-            } else if self.n_consecutive_moves == max_consecutive_moves {
-                match self.direction {
-                    Direction::North | Direction::South => {
-                        vec![Direction::West, Direction::East]
-                    }
-                    Direction::West | Direction::East => {
-                        vec![Direction::North, Direction::South]
-                    }
-                }
-            } else {
-                match self.direction {
-                    Direction::North => {
-                        vec![Direction::North, Direction::West, Direction::East]
-                    }
-                    Direction::West => {
-                        vec![Direction::West, Direction::North, Direction::South]
+        ) -> u32 {
+            self.route(min_consecutive_moves, max_consecutive_moves)
+                .unwrap()
+                .heat_loss
+        }
+
+        /// Finds the route from the top-left to the bottom-right block with the least heat
+        /// loss, where the crucible must move between `min_consecutive_moves` and
+        /// `max_consecutive_moves` blocks before turning (or stopping).
+        ///
+        /// Every straight run is a single edge between states `(block, axis of the run)`, so
+        /// the crucible must turn after each edge. Edge weights are small integers, so
+        /// Dijkstra's algorithm uses a circular bucket queue that spans the largest weight.
+        pub fn route(
+            &self,
+            min_consecutive_moves: usize,
+            max_consecutive_moves: usize,
+        ) -> Option<Route> {
+            let min_consecutive_moves = min_consecutive_moves.max(1);
+            let n_buckets = 9 * max_consecutive_moves + 1;
+            let goal = self.width * self.height - 1;
+
+            // State `2 * block + axis` where the axis is 0 for vertical and 1 for horizontal
+            let mut heat_loss = vec![u32::MAX; 2 * self.width * self.height];
+            let mut predecessors = vec![None; heat_loss.len()];
+            let mut buckets = vec![Vec::new(); n_buckets];
+            heat_loss[0] = 0;
+            heat_loss[1] = 0;
+            buckets[0].extend([0, 1]);
+            let mut n_queued = 2;
+
+            let mut cost = 0;
+            while n_queued > 0 {
+                while let Some(state) = buckets[cost as usize % n_buckets].pop() {
+                    n_queued -= 1;
+                    if heat_loss[state] < cost {
+                        continue;
                     }
-                    Direction::South => {
-                        vec![Direction::South, Direction::West, Direction::East]
+                    let (block, axis) = (state / 2, state % 2);
+                    if block == goal {
+                        return Some(Self::reconstruct(cost, state, &predecessors));
                     }
-                    Direction::East => {
-                        vec![Direction::East, Direction::North, Direction::South]
+
+                    let directions = if axis == 0 {
+                        [Direction::West, Direction::East]
+                    } else {
+                        [Direction::North, Direction::South]
+                    };
+                    for direction in directions {
+                        let mut next_cost = cost;
+                        let mut position = block;
+                        for steps in 1..=max_consecutive_moves {
+                            let Some(next) = self.propagate(position, direction) else {
+                                break;
+                            };
+                            position = next;
+                            next_cost += u32::from(self.heat_loss[position]);
+                            let next_state = 2 * position + (1 - axis);
+                            if steps >= min_consecutive_moves && next_cost < heat_loss[next_state] {
+                                heat_loss[next_state] = next_cost;
+                                predecessors[next_state] = Some((state, Move { direction, steps }));
+                                buckets[next_cost as usize % n_buckets].push(next_state);
+                                n_queued += 1;
+                            }
+                        }
                     }
                 }
+                cost += 1;
             }
+            None
         }
 
-        fn successors(
-            &self,
-            map: &Map,
-            min_consecutive_moves: usize,
-            max_consecutive_moves: usize,
-        ) -> Vec<(Node, u32)> {
-            self.available_directions(min_consecutive_moves, max_consecutive_moves)
+        fn reconstruct(
+            heat_loss: u32,
+            mut state: usize,
+            predecessors: &[Option<(usize, Move)>],
+        ) -> Route {
+            let mut moves = Vec::new();
+            while let Some((previous, m)) = predecessors[state] {
+                moves.push(m);
+                state = previous;
+            }
+            moves.reverse();
+            Route { heat_loss, moves }
+        }
+
+        /// Renders the map with the route drawn like `>v<^`.
+        pub fn render(&self, route: &Route) -> String {
+            let mut symbols = self
+                .heat_loss
                 .iter()
-                .filter_map(|&next_direction| {
-                    map.propagate(self.position, next_direction)
-                        .map(|next_position| {
-                            let next_n_consecutive_moves = if next_direction == self.direction {
-                                self.n_consecutive_moves + 1
-                            } else {
-                                1
-                            };
-                            (
-                                Node::new(next_position, next_direction, next_n_consecutive_moves),
-                                map[next_position],
-                            )
-                        })
-                })
+                .map(|&x| char::from(b'0' + x))
+                .collect_vec();
+            let mut position = 0;
+            for direction in route.directions() {
+                position = self.propagate(position, direction).unwrap();
+                symbols[position] = direction.symbol();
+            }
+            symbols
+                .chunks(self.width)
+                .map(|row| row.iter().collect::<String>() + "\n")
                 .collect()
         }
 
-        fn success(&self, map: &Map, min_consecutive_moves: usize) -> bool {
-            (self.position.0 + 1 == map.columns && self.position.1 + 1 == map.rows)
-                && self.n_consecutive_moves >= min_consecutive_moves
+        fn propagate(&self, position: usize, direction: Direction) -> Option<usize> {
+            let (x, y) = (position % self.width, position / self.width);
+            match direction {
+                Direction::North => (y > 0).then(|| position - self.width),
+                Direction::West => (x > 0).then(|| position - 1),
+                Direction::South => (y + 1 < self.height).then(|| position + self.width),
+                Direction::East => (x + 1 < self.width).then(|| position + 1),
+            }
         }
     }
 }
@@ -201,5 +232,41 @@ mod tests {
     #[test]
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 94);
+        assert_eq!(
+            part2(indoc! {"
+                111111111111
+                999999999991
+                999999999991
+                999999999991
+                999999999991
+            "}),
+            71
+        );
+    }
+
+    #[test]
+    pub fn route_example() {
+        let map = parse(SAMPLE);
+        let route = map.route(0, 3).unwrap();
+        assert_eq!(route.heat_loss, 102);
+        assert_eq!(route.directions().count(), 28);
+        assert_eq!(
+            map.render(&route),
+            indoc! {"
+                2>>34^>>>1323
+                32v>>>35v5623
+                32552456v>>54
+                3446585845v52
+                4546657867v>6
+                14385987984v4
+                44578769877v6
+                36378779796v>
+                465496798688v
+                456467998645v
+                12246868655<v
+                25465488877v5
+                43226746555v>
+            "}
+        );
     }
 }
@@ -1,241 +1,401 @@
 use aoc_runner_derive::aoc;
 
-fn parse(input: &str) -> utils::SortingSystem {
-    let (workflows, part_ratings) = input.split_once("\n\n").unwrap();
-
-    let workflows = workflows
-        .lines()
-        .map(|line| {
-            let (workflow_name, rules) = line.trim_end_matches('}').split_once('{').unwrap();
-            let rules = rules
-                .split(',')
-                .map(|rule| {
-                    if let Some((condition, next_workflow)) = rule.split_once(':') {
-                        let next_workflow = next_workflow.to_owned();
-                        let category = condition.chars().next().unwrap().try_into().unwrap();
-                        let value = condition[2..].parse().unwrap();
-                        if condition.chars().nth(1).unwrap() == '<' {
-                            utils::Condition::Less {
-                                next_workflow,
-                                category,
-                                value,
-                            }
-                        } else {
-                            utils::Condition::Greater {
-                                next_workflow,
-                                category,
-                                value,
-                            }
-                        }
-                    } else {
-                        let next_workflow = rule.to_owned();
-                        utils::Condition::None { next_workflow }
-                    }
-                })
-                .collect();
-            (workflow_name.to_owned(), rules)
-        })
-        .collect();
+use crate::interval::IntervalSet;
 
+fn parse(input: &str) -> (utils::Program, Vec<Vec<i64>>) {
+    let (workflows, part_ratings) = input.split_once("\n\n").unwrap();
+    let program =
+        utils::Program::compile_with_categories(workflows, "in", &["x", "m", "a", "s"]).unwrap();
     let part_ratings = part_ratings
         .lines()
-        .map(|line| {
-            line.trim_start_matches('{')
-                .trim_end_matches('}')
-                .split(',')
-                .map(|l| {
-                    let (category, value) = l.split_once('=').unwrap();
-                    let category = category.chars().next().unwrap().try_into().unwrap();
-                    let value = value.parse().unwrap();
-                    (category, value)
-                })
-                .collect()
-        })
+        .map(|line| program.parse_part(line).unwrap())
         .collect();
-
-    utils::SortingSystem {
-        workflows,
-        part_ratings,
-    }
+    (program, part_ratings)
 }
 
 #[aoc(day19, part1)]
 #[must_use]
 pub fn part1(input: &str) -> u64 {
-    let input = parse(input);
-    utils::count_accepted(&input, "in")
+    let (program, part_ratings) = parse(input);
+    part_ratings
+        .iter()
+        .filter(|part| program.accepts(part))
+        .map(|part| part.iter().sum::<i64>())
+        .sum::<i64>()
+        .try_into()
+        .unwrap()
 }
 
 #[aoc(day19, part2)]
 #[must_use]
 pub fn part2(input: &str) -> u64 {
-    let input = parse(input);
-    use strum::IntoEnumIterator;
-    utils::count_all_accepted_combinations_recursively(
-        &input.workflows,
-        utils::Category::iter().map(|ch| (ch, (1, 4000))).collect(),
-        "in",
-    )
+    let (program, _) = parse(input);
+    program
+        .acceptance_region(&IntervalSet::from(1..4001))
+        .iter()
+        .map(|rectangle| rectangle.iter().map(IntervalSet::len).product::<u64>())
+        .sum()
 }
 
-mod utils {
-    use strum::EnumIter;
+pub mod utils {
+    use crate::interval::IntervalSet;
 
-    pub struct SortingSystem {
-        pub workflows: rustc_hash::FxHashMap<String, smallvec::SmallVec<[Condition; 4]>>,
-        pub part_ratings: Vec<rustc_hash::FxHashMap<Category, u64>>,
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum CompileError {
+        Syntax(String),
+        UnknownWorkflow(String),
+        DuplicateWorkflow(String),
+        /// Workflows that form a cycle, in the order they are visited.
+        Cycle(Vec<String>),
     }
 
-    #[derive(Clone, PartialEq, Eq, Hash)]
-    pub enum Condition {
-        Less {
-            next_workflow: String,
-            category: Category,
-            value: u64,
-        },
-        Greater {
-            next_workflow: String,
-            category: Category,
-            value: u64,
-        },
-        None {
-            next_workflow: String,
-        },
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Target {
+        Accept,
+        Reject,
+        /// Index of the first rule of a workflow.
+        Rule(usize),
     }
 
-    #[repr(u8)]
-    #[derive(Clone, Copy, PartialEq, Eq, Hash, EnumIter)]
-    pub enum Category {
-        X = b'x',
-        M = b'm',
-        A = b'a',
-        S = b's',
+    /// Compiled rule that jumps to its target if the condition holds (or if it has no
+    /// condition) and falls through to the next rule of its workflow otherwise.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Rule {
+        pub workflow: usize,
+        /// Category and the set of ratings for which the condition holds.
+        pub condition: Option<(usize, IntervalSet)>,
+        pub target: Target,
     }
 
-    impl TryFrom<char> for Category {
-        type Error = &'static str;
-        fn try_from(value: char) -> Result<Self, Self::Error> {
-            match value {
-                'x' => Ok(Category::X),
-                'm' => Ok(Category::M),
-                'a' => Ok(Category::A),
-                's' => Ok(Category::S),
-                _ => Err("Invalid category"),
+    /// Workflows lowered into a decision tree over interned workflows and categories.
+    #[derive(Clone, Debug)]
+    pub struct Program {
+        categories: Vec<String>,
+        category_ids: rustc_hash::FxHashMap<String, usize>,
+        workflows: Vec<String>,
+        workflow_ids: rustc_hash::FxHashMap<String, usize>,
+        /// Index of the first rule of each workflow.
+        entries: Vec<usize>,
+        rules: Vec<Rule>,
+        start: usize,
+    }
+
+    impl Program {
+        pub fn compile(input: &str, start: &str) -> Result<Self, CompileError> {
+            Self::compile_with_categories(input, start, &[])
+        }
+
+        /// Compiles the workflows with the given categories interned first, so that ratings
+        /// of categories that no rule refers to are part of the acceptance region.
+        pub fn compile_with_categories(
+            input: &str,
+            start: &str,
+            categories: &[&str],
+        ) -> Result<Self, CompileError> {
+            let mut program = Self {
+                categories: Vec::new(),
+                category_ids: rustc_hash::FxHashMap::default(),
+                workflows: Vec::new(),
+                workflow_ids: rustc_hash::FxHashMap::default(),
+                entries: Vec::new(),
+                rules: Vec::new(),
+                start: 0,
+            };
+            for category in categories {
+                program.intern_category(category);
+            }
+
+            // Intern all workflow names first so that rules can refer to them
+            let workflows = input
+                .lines()
+                .map(|line| {
+                    line.trim_end_matches('}')
+                        .split_once('{')
+                        .ok_or_else(|| CompileError::Syntax(line.to_owned()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut entries = Vec::with_capacity(workflows.len());
+            for &(name, rules) in &workflows {
+                if program
+                    .workflow_ids
+                    .insert(name.to_owned(), program.workflows.len())
+                    .is_some()
+                {
+                    return Err(CompileError::DuplicateWorkflow(name.to_owned()));
+                }
+                program.workflows.push(name.to_owned());
+                entries.push(program.rules.len());
+                program.rules.extend(rules.split(',').map(|_| Rule {
+                    workflow: program.workflows.len() - 1,
+                    condition: None,
+                    target: Target::Reject,
+                }));
             }
+            program.entries = entries;
+
+            let mut i = 0;
+            for (workflow, &(_, rules)) in workflows.iter().enumerate() {
+                let rules = rules.split(',').collect::<Vec<_>>();
+                for (j, rule) in rules.iter().enumerate() {
+                    let (condition, target) = match rule.split_once(':') {
+                        Some((condition, target)) => {
+                            (Some(program.parse_condition(condition)?), target)
+                        }
+                        None if j + 1 == rules.len() => (None, *rule),
+                        None => return Err(CompileError::Syntax((*rule).to_owned())),
+                    };
+                    if condition.is_some() && j + 1 == rules.len() {
+                        return Err(CompileError::Syntax((*rule).to_owned()));
+                    }
+                    program.rules[i] = Rule {
+                        workflow,
+                        condition,
+                        target: program.resolve(target)?,
+                    };
+                    i += 1;
+                }
+            }
+
+            program.start = program.workflow_id(start)?;
+            if let Some(cycle) = program.find_cycle() {
+                return Err(CompileError::Cycle(cycle));
+            }
+            Ok(program)
         }
-    }
 
-    pub fn count_accepted(sorting_system: &SortingSystem, first_workflow: &str) -> u64 {
-        sorting_system
-            .part_ratings
-            .iter()
-            .map(|rating| {
-                let mut current_workflow = first_workflow;
-                loop {
-                    match current_workflow {
-                        "A" => break rating.values().sum(),
-                        "R" => break 0,
-                        _ => {}
+        fn parse_condition(
+            &mut self,
+            condition: &str,
+        ) -> Result<(usize, IntervalSet), CompileError> {
+            let error = || CompileError::Syntax(condition.to_owned());
+            let split = condition.find(['<', '>', '=']).ok_or_else(error)?;
+            let (category, condition) = condition.split_at(split);
+            let (comparator, value) = ["<=", ">=", "==", "<", ">"]
+                .into_iter()
+                .find_map(|comparator| {
+                    condition
+                        .strip_prefix(comparator)
+                        .map(|value| (comparator, value))
+                })
+                .ok_or_else(error)?;
+            let value: i64 = value.parse().map_err(|_| error())?;
+            let bounds = match comparator {
+                "<" => i64::MIN..value,
+                "<=" => i64::MIN..value.saturating_add(1),
+                ">" => value.saturating_add(1)..i64::MAX,
+                ">=" => value..i64::MAX,
+                _ => value..value.saturating_add(1),
+            };
+            Ok((self.intern_category(category), bounds.into()))
+        }
+
+        fn intern_category(&mut self, category: &str) -> usize {
+            if let Some(&id) = self.category_ids.get(category) {
+                return id;
+            }
+            self.category_ids
+                .insert(category.to_owned(), self.categories.len());
+            self.categories.push(category.to_owned());
+            self.categories.len() - 1
+        }
+
+        fn workflow_id(&self, name: &str) -> Result<usize, CompileError> {
+            self.workflow_ids
+                .get(name)
+                .copied()
+                .ok_or_else(|| CompileError::UnknownWorkflow(name.to_owned()))
+        }
+
+        /// Indices of the rules of the workflow.
+        fn workflow_rules(&self, workflow: usize) -> std::ops::Range<usize> {
+            let end = self
+                .entries
+                .get(workflow + 1)
+                .copied()
+                .unwrap_or(self.rules.len());
+            self.entries[workflow]..end
+        }
+
+        fn resolve(&self, target: &str) -> Result<Target, CompileError> {
+            match target {
+                "A" => Ok(Target::Accept),
+                "R" => Ok(Target::Reject),
+                name => Ok(Target::Rule(self.entries[self.workflow_id(name)?])),
+            }
+        }
+
+        fn find_cycle(&self) -> Option<Vec<String>> {
+            #[derive(Clone, Copy)]
+            enum State {
+                Unvisited,
+                /// On the current path at the given index.
+                OnPath(usize),
+                Done,
+            }
+
+            fn visit(
+                program: &Program,
+                workflow: usize,
+                state: &mut [State],
+                path: &mut Vec<usize>,
+            ) -> Option<Vec<String>> {
+                match state[workflow] {
+                    State::OnPath(start) => {
+                        return Some(
+                            path[start..]
+                                .iter()
+                                .map(|&w| program.workflows[w].clone())
+                                .collect(),
+                        );
                     }
-                    for condition in sorting_system.workflows.get(current_workflow).unwrap() {
-                        match condition {
-                            Condition::Less {
-                                next_workflow,
-                                category,
-                                value,
-                            } => {
-                                if rating.get(category).unwrap() < value {
-                                    current_workflow = next_workflow;
-                                    break;
-                                }
-                            }
-                            Condition::Greater {
-                                next_workflow,
-                                category,
-                                value,
-                            } => {
-                                if rating.get(category).unwrap() > value {
-                                    current_workflow = next_workflow;
-                                    break;
-                                }
-                            }
-                            Condition::None { next_workflow } => {
-                                current_workflow = next_workflow;
-                                break;
-                            }
+                    State::Done => return None,
+                    State::Unvisited => {}
+                }
+                state[workflow] = State::OnPath(path.len());
+                path.push(workflow);
+                for i in program.workflow_rules(workflow) {
+                    if let Target::Rule(next) = program.rules[i].target {
+                        if let Some(cycle) =
+                            visit(program, program.rules[next].workflow, state, path)
+                        {
+                            return Some(cycle);
                         }
                     }
                 }
-            })
-            .sum()
-    }
+                path.pop();
+                state[workflow] = State::Done;
+                None
+            }
+
+            let mut state = vec![State::Unvisited; self.workflows.len()];
+            (0..self.workflows.len())
+                .find_map(|workflow| visit(self, workflow, &mut state, &mut Vec::new()))
+        }
 
-    pub fn count_all_accepted_combinations_recursively(
-        workflows: &rustc_hash::FxHashMap<String, smallvec::SmallVec<[Condition; 4]>>,
-        mut ranges: rustc_hash::FxHashMap<Category, (u64, u64)>,
-        current_workflow: &str,
-    ) -> u64 {
-        match current_workflow {
-            "A" => {
-                return ranges
-                    .values()
-                    .map(|(start, end)| end - start + 1)
-                    .product()
+        #[must_use]
+        pub fn categories(&self) -> &[String] {
+            &self.categories
+        }
+
+        #[must_use]
+        pub fn rules(&self) -> &[Rule] {
+            &self.rules
+        }
+
+        /// Parses ratings such as `{x=787,m=2655}` indexed by the interned categories.
+        pub fn parse_part(&self, line: &str) -> Result<Vec<i64>, CompileError> {
+            let mut part = vec![0; self.categories.len()];
+            for rating in line
+                .trim_start_matches('{')
+                .trim_end_matches('}')
+                .split(',')
+            {
+                let error = || CompileError::Syntax(rating.to_owned());
+                let (category, value) = rating.split_once('=').ok_or_else(error)?;
+                let value = value.parse().map_err(|_| error())?;
+                // Categories that no rule refers to still count towards the total rating
+                match self.category_ids.get(category) {
+                    Some(&i) => part[i] = value,
+                    None => part.push(value),
+                }
             }
-            "R" => return 0,
-            _ => {}
+            Ok(part)
         }
 
-        workflows[current_workflow]
-            .iter()
-            .map(|condition| match condition {
-                Condition::Less {
-                    next_workflow,
-                    category,
-                    value,
-                } => {
-                    let &(start, end) = ranges.get(category).unwrap();
-
-                    let within_bounds = (start, (value.saturating_sub(1)).min(end));
-                    ranges.insert(*category, within_bounds);
-                    let ranges_within_bounds = ranges.clone();
-
-                    let outside_bounds = (*value.max(&start), end);
-                    ranges.insert(*category, outside_bounds);
-
-                    count_all_accepted_combinations_recursively(
-                        workflows,
-                        ranges_within_bounds,
-                        next_workflow,
-                    )
+        #[must_use]
+        pub fn accepts(&self, part: &[i64]) -> bool {
+            let mut i = self.entries[self.start];
+            loop {
+                let rule = &self.rules[i];
+                let target = match &rule.condition {
+                    Some((category, bounds)) if !bounds.contains(part[*category]) => {
+                        i += 1;
+                        continue;
+                    }
+                    _ => rule.target,
+                };
+                match target {
+                    Target::Accept => return true,
+                    Target::Reject => return false,
+                    Target::Rule(next) => i = next,
                 }
-                Condition::Greater {
-                    next_workflow,
-                    category,
-                    value,
-                } => {
-                    let &(start, end) = ranges.get(category).unwrap();
-
-                    let within_bounds = ((value.saturating_add(1)).max(start), end);
-                    ranges.insert(*category, within_bounds);
-                    let ranges_within_bounds = ranges.clone();
-
-                    let outside_bounds = (start, *value.min(&end));
-                    ranges.insert(*category, outside_bounds);
-
-                    count_all_accepted_combinations_recursively(
-                        workflows,
-                        ranges_within_bounds,
-                        next_workflow,
+            }
+        }
+
+        /// Flattens the accepted ratings into disjoint hyper-rectangles (one set per category).
+        #[must_use]
+        pub fn acceptance_region(&self, domain: &IntervalSet) -> Vec<Vec<IntervalSet>> {
+            self.walk(domain).0
+        }
+
+        /// Returns the `(workflow, rule index)` pairs that never apply to any rating within the domain.
+        #[must_use]
+        pub fn unreachable_rules(&self, domain: &IntervalSet) -> Vec<(&str, usize)> {
+            let reached = self.walk(domain).1;
+            (0..self.rules.len())
+                .filter(|&i| !reached[i])
+                .map(|i| {
+                    let workflow = self.rules[i].workflow;
+                    (
+                        self.workflows[workflow].as_str(),
+                        i - self.entries[workflow],
                     )
+                })
+                .collect()
+        }
+
+        fn walk(&self, domain: &IntervalSet) -> (Vec<Vec<IntervalSet>>, Vec<bool>) {
+            let mut regions = Vec::new();
+            let mut reached = vec![false; self.rules.len()];
+            let mut stack = vec![(
+                self.entries[self.start],
+                vec![domain.clone(); self.categories.len()],
+            )];
+            while let Some((i, mut rectangle)) = stack.pop() {
+                let rule = &self.rules[i];
+                let matched = match &rule.condition {
+                    Some((category, bounds)) => {
+                        let mut matched = rectangle.clone();
+                        matched[*category] = rectangle[*category].intersection(bounds);
+                        rectangle[*category] = rectangle[*category].difference(bounds);
+                        if !rectangle[*category].is_empty() {
+                            stack.push((i + 1, rectangle));
+                        }
+                        matched
+                    }
+                    None => rectangle,
+                };
+                if matched.iter().any(IntervalSet::is_empty) {
+                    continue;
                 }
-                Condition::None { next_workflow } => count_all_accepted_combinations_recursively(
-                    workflows,
-                    ranges.clone(),
-                    next_workflow,
-                ),
-            })
-            .sum()
+                reached[i] = true;
+                match rule.target {
+                    Target::Accept => regions.push(matched),
+                    Target::Reject => {}
+                    Target::Rule(next) => stack.push((next, matched)),
+                }
+            }
+            (regions, reached)
+        }
+
+        /// Formats a hyper-rectangle in the same notation as part ratings.
+        #[must_use]
+        pub fn format_region(&self, rectangle: &[IntervalSet]) -> String {
+            let ratings = self
+                .categories
+                .iter()
+                .zip(rectangle)
+                .map(|(category, set)| {
+                    let ranges = set
+                        .ranges()
+                        .iter()
+                        .map(|range| format!("{}..={}", range.start, range.end - 1))
+                        .collect::<Vec<_>>();
+                    format!("{category}={}", ranges.join("|"))
+                })
+                .collect::<Vec<_>>();
+            format!("{{{}}}", ratings.join(","))
+        }
     }
 }
 
@@ -273,4 +433,81 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 167_409_079_868_000);
     }
+
+    #[test]
+    pub fn unreferenced_categories() {
+        // Categories that no rule refers to still span the whole domain
+        assert_eq!(
+            part2("in{x>10:A,R}\n\n{x=1,m=2,a=3,s=4}\n"),
+            3990 * 4000_u64.pow(3)
+        );
+        assert_eq!(part1("in{x>10:A,R}\n\n{x=11,m=2,a=3,s=4}\n"), 20);
+    }
+
+    #[test]
+    pub fn unreachable_rules() {
+        let program = utils::Program::compile(
+            indoc! {"
+                in{x<10:A,x<5:R,foo}
+                foo{m>=3:A,m<=2:R,R}
+            "},
+            "in",
+        )
+        .unwrap();
+        assert_eq!(
+            program.unreachable_rules(&IntervalSet::from(1..4001)),
+            [("in", 1), ("foo", 2)]
+        );
+        assert!(
+            utils::Program::compile(SAMPLE.split_once("\n\n").unwrap().0, "in")
+                .unwrap()
+                .unreachable_rules(&IntervalSet::from(1..4001))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    pub fn cycles() {
+        assert_eq!(
+            utils::Program::compile("in{x<10:a,R}\na{b}\nb{m==3:in,A}", "in").unwrap_err(),
+            utils::CompileError::Cycle(vec!["in".to_owned(), "a".to_owned(), "b".to_owned()])
+        );
+        assert_eq!(
+            utils::Program::compile("in{x<10:R,a}\na{b}\nb{s>5:a,A}", "in").unwrap_err(),
+            utils::CompileError::Cycle(vec!["a".to_owned(), "b".to_owned()])
+        );
+        assert_eq!(
+            utils::Program::compile("in{x<10:a,R}", "in").unwrap_err(),
+            utils::CompileError::UnknownWorkflow("a".to_owned())
+        );
+        assert_eq!(
+            utils::Program::compile("in{a}\na{A}\na{R}", "in").unwrap_err(),
+            utils::CompileError::DuplicateWorkflow("a".to_owned())
+        );
+        assert!(utils::Program::compile("in{x<=9223372036854775807:A,R}", "in").is_ok());
+    }
+
+    #[test]
+    pub fn arbitrary_categories() {
+        let program = utils::Program::compile(
+            "in{speed>=100:A,weight==7:check,R}\ncheck{speed<=3:R,A}",
+            "in",
+        )
+        .unwrap();
+        assert!(program.accepts(&program.parse_part("{speed=100,weight=1}").unwrap()));
+        assert!(program.accepts(&program.parse_part("{speed=4,weight=7}").unwrap()));
+        assert!(!program.accepts(&program.parse_part("{speed=3,weight=7}").unwrap()));
+
+        let regions = program.acceptance_region(&IntervalSet::from(1..1000));
+        assert_eq!(
+            regions
+                .iter()
+                .map(|region| program.format_region(region))
+                .collect::<Vec<_>>(),
+            [
+                "{speed=100..=999,weight=1..=999}",
+                "{speed=4..=99,weight=7..=7}"
+            ]
+        );
+    }
 }
@@ -1,239 +1,342 @@
 use aoc_runner_derive::aoc;
 
 fn parse(input: &str) -> utils::Modules {
-    let mut modules: rustc_hash::FxHashMap<String, utils::Module> = input
-        .lines()
-        .map(|line| {
-            let (module_id, outputs) = line.split_once("->").unwrap();
-            let outputs = outputs.split(',').map(|s| s.trim().to_owned()).collect();
-
-            let module_id = module_id.trim();
-            match module_id {
-                broadcaster if broadcaster == "broadcaster" => {
-                    (broadcaster.to_owned(), utils::Module::Broadcast { outputs })
-                }
-                flipflop if flipflop.starts_with('%') => (
-                    flipflop.chars().skip(1).collect(),
-                    utils::Module::FlipFlop {
-                        state: false,
-                        outputs,
-                    },
-                ),
-                conjunction if conjunction.starts_with('&') => (
-                    conjunction.chars().skip(1).collect(),
-                    utils::Module::Conjunction {
-                        input_memory: rustc_hash::FxHashMap::default(),
-                        outputs,
-                    },
-                ),
-                _ => unreachable!(),
-            }
-        })
-        .collect();
-
-    // Create the final "rx" module (only found in outputs) and update the input memory of all conjunctions
-    modules.clone().iter().for_each(|(name, module)| {
-        module.outputs().iter().for_each(|output| {
-            if let utils::Module::Conjunction { input_memory, .. } = modules
-                .entry(output.clone())
-                .or_insert(utils::Module::Final { activated: false })
-            {
-                input_memory.insert(name.clone(), utils::Pulse::Low);
-            }
-        });
-    });
-
-    utils::Modules::new(modules)
+    utils::Modules::parse(input).unwrap()
 }
 
 #[aoc(day20, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let mut input = parse(input);
-
-    let (n_low, n_high) = (0..1000).fold((0, 0), |(mut acc_low, mut acc_high), _| {
-        let mut active_pulses = std::collections::VecDeque::default();
-        active_pulses.push_back((
-            "button".to_owned(),
-            "broadcaster".to_owned(),
-            utils::Pulse::Low,
-        ));
-        while let Some((source_module, target_module, pulse)) = active_pulses.pop_front() {
-            input
-                .get_mut(&target_module)
-                .unwrap()
-                .propagate_pulse(&source_module, pulse)
-                .into_iter()
-                .for_each(|(next_target_module, next_pulse)| {
-                    let next_source_module = target_module.clone();
-                    active_pulses.push_back((next_source_module, next_target_module, next_pulse));
-                });
-            match pulse {
-                utils::Pulse::Low => acc_low += 1,
-                utils::Pulse::High => acc_high += 1,
-            }
-        }
-        (acc_low, acc_high)
+    let mut modules = parse(input);
+    let (n_low, n_high) = (0..1000).fold((0, 0), |(n_low, n_high), _| {
+        let history = modules.press();
+        let n_high_press = history
+            .iter()
+            .filter(|record| record.pulse == utils::Pulse::High)
+            .count();
+        (n_low + history.len() - n_high_press, n_high + n_high_press)
     });
-
     n_low * n_high
 }
 
 #[aoc(day20, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    let mut input = parse(input);
-
-    // Find all modules that branch into a conjunction that is connected to the final "rx" module
-    let mut final_module_sources = vec!["rx".to_string()];
-    while final_module_sources.len() == 1 {
-        let final_module_source = final_module_sources.pop().unwrap();
-        match input[&final_module_source] {
-            utils::Module::Conjunction { .. } | utils::Module::Final { .. } => {}
-            _ => {
-                unimplemented!("It is assumed that a single conjunction is connected to the final \"rx\" module");
-            }
-        }
-        final_module_sources = input
-            .iter()
-            .filter_map(|(name, module)| {
-                module
-                    .outputs()
-                    .contains(&final_module_source)
-                    .then_some(name.clone())
-            })
-            .collect();
-    }
-    let mut final_module_source_loop_lengths: rustc_hash::FxHashMap<String, usize> =
-        final_module_sources
-            .iter()
-            .map(|name| (name.clone(), 0))
-            .collect();
-
-    // Keep pressing the button until all loops lengths are determined
-    'outer: for i in 1.. {
-        let mut active_pulses = std::collections::VecDeque::default();
-        active_pulses.push_back((
-            "button".to_owned(),
-            "broadcaster".to_owned(),
-            utils::Pulse::Low,
-        ));
-        while let Some((source_module, target_module, pulse)) = active_pulses.pop_front() {
-            for (next_target_module, next_pulse) in input
-                .get_mut(&target_module)
-                .unwrap()
-                .propagate_pulse(&source_module, pulse)
-            {
-                let next_source_module = target_module.clone();
-                active_pulses.push_back((next_source_module, next_target_module, next_pulse));
-
-                // Only high pulses are considered because it is assumed that a single conjunction is connected to the final "rx" module
-                if final_module_sources.contains(&source_module) && pulse == utils::Pulse::High {
-                    final_module_source_loop_lengths.insert(source_module.clone(), i);
-
-                    // Break once the length of all loops is determined
-                    if final_module_source_loop_lengths.values().all(|x| *x > 1) {
-                        break 'outer;
-                    }
-                }
-            }
-        }
-    }
-
-    // Find the least common multiple of all loop lengths
-    final_module_source_loop_lengths
-        .values()
-        .fold(1, |acc, &x| num::integer::lcm(acc, x))
+    parse(input).presses_until_low("rx", 1 << 16).unwrap()
 }
 
-mod utils {
-    #[derive(Clone, derive_more::Deref, derive_more::DerefMut)]
-    pub struct Modules(rustc_hash::FxHashMap<String, Module>);
+pub mod utils {
+    use crate::modular;
 
-    impl Modules {
-        pub fn new(value: rustc_hash::FxHashMap<String, Module>) -> Self {
-            Self(value)
-        }
-    }
-
-    #[derive(Clone, Copy, PartialEq, Eq)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub enum Pulse {
         Low,
         High,
     }
 
-    #[derive(Clone)]
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Error {
+        Syntax(String),
+        UnknownModule(String),
+        /// The network does not have the structure that the analysis relies on.
+        Unsupported(&'static str),
+        /// The output of the sub-circuit does not repeat with a constant period.
+        NotPeriodic(String),
+        /// The output of the sub-circuit was not observed often enough to determine its period.
+        NoCycle(String),
+        /// The periods of the sub-circuits never align.
+        NoSolution,
+    }
+
+    #[derive(Clone, Debug)]
     pub enum Module {
-        Broadcast {
-            outputs: smallvec::SmallVec<[String; 7]>,
-        },
+        Broadcast,
         FlipFlop {
-            outputs: smallvec::SmallVec<[String; 7]>,
             state: bool,
         },
         Conjunction {
-            outputs: smallvec::SmallVec<[String; 7]>,
-            input_memory: rustc_hash::FxHashMap<String, Pulse>,
-        },
-        Final {
-            activated: bool,
+            input_memory: rustc_hash::FxHashMap<usize, Pulse>,
         },
+        /// Module that is only found in outputs.
+        Final,
+    }
+
+    /// Pulse sent from one module to another (`from` is `None` for the button).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct PulseRecord {
+        pub from: Option<usize>,
+        pub to: usize,
+        pub pulse: Pulse,
+    }
+
+    /// Part of the network that is driven by a single output of the broadcaster and that is
+    /// independent of all other sub-circuits until it feeds the final conjunction.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct SubCircuit {
+        pub input: usize,
+        pub output: usize,
+        pub modules: Vec<usize>,
     }
 
-    impl Module {
-        pub fn outputs(&self) -> &[String] {
-            match self {
-                Module::Broadcast { outputs }
-                | Module::FlipFlop { outputs, .. }
-                | Module::Conjunction { outputs, .. } => outputs,
-                Module::Final { .. } => &[],
+    /// Presses at which a sub-circuit sends a high pulse, i.e. `first + k * period`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Cycle {
+        pub output: usize,
+        pub first: usize,
+        pub period: usize,
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct Modules {
+        names: Vec<String>,
+        ids: rustc_hash::FxHashMap<String, usize>,
+        modules: Vec<Module>,
+        outputs: Vec<smallvec::SmallVec<[usize; 7]>>,
+        inputs: Vec<smallvec::SmallVec<[usize; 7]>>,
+        broadcaster: usize,
+    }
+
+    impl Modules {
+        pub fn parse(input: &str) -> Result<Self, Error> {
+            let mut network = Self {
+                names: Vec::new(),
+                ids: rustc_hash::FxHashMap::default(),
+                modules: Vec::new(),
+                outputs: Vec::new(),
+                inputs: Vec::new(),
+                broadcaster: 0,
+            };
+
+            let lines = input
+                .lines()
+                .map(|line| {
+                    let (module, outputs) = line
+                        .split_once("->")
+                        .ok_or_else(|| Error::Syntax(line.to_owned()))?;
+                    let module = module.trim();
+                    let (name, module) = if let Some(name) = module.strip_prefix('%') {
+                        (name, Module::FlipFlop { state: false })
+                    } else if let Some(name) = module.strip_prefix('&') {
+                        (
+                            name,
+                            Module::Conjunction {
+                                input_memory: rustc_hash::FxHashMap::default(),
+                            },
+                        )
+                    } else if module == "broadcaster" {
+                        (module, Module::Broadcast)
+                    } else {
+                        return Err(Error::Syntax(line.to_owned()));
+                    };
+                    let id = network.intern(name);
+                    network.modules[id] = module;
+                    Ok((id, outputs))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // Connect the modules, creating the final modules that are only found in outputs
+            for (id, outputs) in lines {
+                for output in outputs.split(',').map(str::trim) {
+                    let output = network.intern(output);
+                    network.outputs[id].push(output);
+                    network.inputs[output].push(id);
+                    if let Module::Conjunction { input_memory } = &mut network.modules[output] {
+                        input_memory.insert(id, Pulse::Low);
+                    }
+                }
             }
+            for (id, inputs) in network.inputs.iter().enumerate() {
+                if let Module::Conjunction { input_memory } = &mut network.modules[id] {
+                    input_memory.extend(inputs.iter().map(|&input| (input, Pulse::Low)));
+                }
+            }
+
+            network.broadcaster = network.id("broadcaster")?;
+            Ok(network)
         }
 
-        pub fn propagate_pulse(
-            &mut self,
-            source_module: &str,
-            pulse: Pulse,
-        ) -> smallvec::SmallVec<[(String, Pulse); 7]> {
-            match self {
-                Module::Broadcast { outputs } => {
-                    return outputs
-                        .iter()
-                        .map(|output| (output.to_owned(), pulse))
-                        .collect();
-                }
-                Module::FlipFlop { outputs, state } => {
-                    if pulse == Pulse::Low {
-                        let new_pulse = if *state { Pulse::Low } else { Pulse::High };
+        fn intern(&mut self, name: &str) -> usize {
+            *self.ids.entry(name.to_owned()).or_insert_with(|| {
+                self.names.push(name.to_owned());
+                self.modules.push(Module::Final);
+                self.outputs.push(smallvec::SmallVec::new());
+                self.inputs.push(smallvec::SmallVec::new());
+                self.names.len() - 1
+            })
+        }
+
+        pub fn id(&self, name: &str) -> Result<usize, Error> {
+            self.ids
+                .get(name)
+                .copied()
+                .ok_or_else(|| Error::UnknownModule(name.to_owned()))
+        }
+
+        #[must_use]
+        pub fn name(&self, id: usize) -> &str {
+            &self.names[id]
+        }
+
+        /// Presses the button and returns the history of all pulses in the order they are sent.
+        pub fn press(&mut self) -> Vec<PulseRecord> {
+            let mut history = vec![PulseRecord {
+                from: None,
+                to: self.broadcaster,
+                pulse: Pulse::Low,
+            }];
+            let mut i = 0;
+            while let Some(&PulseRecord { from, to, pulse }) = history.get(i) {
+                i += 1;
+                let next_pulse = match &mut self.modules[to] {
+                    Module::Broadcast => Some(pulse),
+                    Module::FlipFlop { state } => (pulse == Pulse::Low).then(|| {
                         *state = !*state;
-                        return outputs
-                            .iter()
-                            .map(|output| (output.to_owned(), new_pulse))
-                            .collect();
+                        if *state {
+                            Pulse::High
+                        } else {
+                            Pulse::Low
+                        }
+                    }),
+                    Module::Conjunction { input_memory } => {
+                        input_memory.insert(from.unwrap(), pulse);
+                        Some(if input_memory.values().all(|&x| x == Pulse::High) {
+                            Pulse::Low
+                        } else {
+                            Pulse::High
+                        })
                     }
+                    Module::Final => None,
+                };
+                if let Some(next_pulse) = next_pulse {
+                    history.extend(self.outputs[to].iter().map(|&output| PulseRecord {
+                        from: Some(to),
+                        to: output,
+                        pulse: next_pulse,
+                    }));
                 }
-                Module::Conjunction {
-                    outputs,
-                    input_memory,
-                } => {
-                    *input_memory.get_mut(source_module).unwrap() = pulse;
-                    let pulse = if input_memory.values().all(|v| *v == Pulse::High) {
-                        Pulse::Low
-                    } else {
-                        Pulse::High
-                    };
-                    return outputs
-                        .iter()
-                        .map(|output| (output.to_owned(), pulse))
-                        .collect();
+            }
+            history
+        }
+
+        /// Decomposes the network into independent sub-circuits that feed the single
+        /// conjunction connected to the target.
+        pub fn sub_circuits(&self, target: &str) -> Result<Vec<SubCircuit>, Error> {
+            let target = self.id(target)?;
+            let &[conjunction] = self.inputs[target].as_slice() else {
+                return Err(Error::Unsupported(
+                    "The target must be connected to a single module",
+                ));
+            };
+            if !matches!(self.modules[conjunction], Module::Conjunction { .. }) {
+                return Err(Error::Unsupported(
+                    "The target must be connected to a conjunction",
+                ));
+            }
+
+            let reachable = self.outputs[self.broadcaster]
+                .iter()
+                .map(|&input| {
+                    let mut visited = vec![false; self.names.len()];
+                    let mut stack = vec![input];
+                    visited[input] = true;
+                    while let Some(id) = stack.pop() {
+                        for &output in &self.outputs[id] {
+                            if !visited[output] {
+                                visited[output] = true;
+                                stack.push(output);
+                            }
+                        }
+                    }
+                    (input, visited)
+                })
+                .collect::<Vec<_>>();
+            let is_shared = |id: usize| reachable.iter().filter(|(_, x)| x[id]).count() > 1;
+
+            let mut sub_circuits = Vec::<SubCircuit>::new();
+            for &output in &self.inputs[conjunction] {
+                let mut owners = reachable.iter().filter(|(_, visited)| visited[output]);
+                let (Some(&(input, ref visited)), None) = (owners.next(), owners.next()) else {
+                    return Err(Error::Unsupported(
+                        "Each input of the conjunction must be driven by a single sub-circuit",
+                    ));
+                };
+                if sub_circuits.iter().any(|x| x.input == input) {
+                    return Err(Error::Unsupported(
+                        "Each sub-circuit must drive a single input of the conjunction",
+                    ));
                 }
-                Module::Final { activated } => {
-                    if pulse == Pulse::Low {
-                        *activated = true;
+                sub_circuits.push(SubCircuit {
+                    input,
+                    output,
+                    modules: (0..self.names.len())
+                        .filter(|&id| visited[id] && !is_shared(id))
+                        .collect(),
+                });
+            }
+            Ok(sub_circuits)
+        }
+
+        /// Detects the period and phase of each sub-circuit by simulating up to `max_presses`
+        /// button presses (starting from the current state).
+        pub fn cycles(&self, target: &str, max_presses: usize) -> Result<Vec<Cycle>, Error> {
+            let sub_circuits = self.sub_circuits(target)?;
+            let conjunction = self.inputs[self.id(target)?][0];
+
+            let mut network = self.clone();
+            let mut hits = vec![smallvec::SmallVec::<[usize; 3]>::new(); sub_circuits.len()];
+            for press in 1..=max_presses {
+                for record in network.press() {
+                    if record.to != conjunction || record.pulse != Pulse::High {
+                        continue;
+                    }
+                    let i = sub_circuits
+                        .iter()
+                        .position(|x| Some(x.output) == record.from)
+                        .unwrap();
+                    if hits[i].len() < 3 && hits[i].last() != Some(&press) {
+                        hits[i].push(press);
                     }
                 }
+                if hits.iter().all(|x| x.len() == 3) {
+                    break;
+                }
             }
-            smallvec::SmallVec::default()
+
+            sub_circuits
+                .iter()
+                .zip(hits)
+                .map(|(sub_circuit, hits)| {
+                    let &[first, second, third] = hits.as_slice() else {
+                        return Err(Error::NoCycle(self.names[sub_circuit.output].clone()));
+                    };
+                    if third - second != second - first {
+                        return Err(Error::NotPeriodic(self.names[sub_circuit.output].clone()));
+                    }
+                    Ok(Cycle {
+                        output: sub_circuit.output,
+                        first,
+                        period: second - first,
+                    })
+                })
+                .collect()
+        }
+
+        /// Determines the number of button presses until the target receives a low pulse by
+        /// combining the cycles of all sub-circuits.
+        ///
+        /// It is assumed that the target receives a low pulse once all sub-circuits send a high
+        /// pulse during the same button press.
+        pub fn presses_until_low(&self, target: &str, max_presses: usize) -> Result<usize, Error> {
+            let cycles = self.cycles(target, max_presses)?;
+            let min = cycles.iter().map(|cycle| cycle.first).max().unwrap_or(0);
+            modular::earliest_common(
+                cycles
+                    .iter()
+                    .map(|cycle| (cycle.first as i128, cycle.period as i128)),
+                min as i128,
+            )
+            .ok_or(Error::NoSolution)
+            .map(|presses| presses.try_into().unwrap())
         }
     }
 }
@@ -243,7 +346,7 @@ mod tests {
     use super::*;
     use indoc::indoc;
 
-    const SAMPLES: [&str; 2] = [
+    const SAMPLES: [&str; 3] = [
         indoc! {"
             broadcaster -> a, b, c
             %a -> b
@@ -258,6 +361,19 @@ mod tests {
             %b -> con
             &con -> outputs
         "},
+        indoc! {"
+            broadcaster -> a1, b1
+            %a1 -> a2, ca
+            %a2 -> ca
+            &ca -> ia
+            &ia -> zz
+            %b1 -> b2, cb
+            %b2 -> b3, cb
+            %b3 -> cb
+            &cb -> ib
+            &ib -> zz
+            &zz -> rx
+        "},
     ];
 
     #[test]
@@ -267,6 +383,78 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
-    pub fn part2_example() {}
+    pub fn part2_example() {
+        // The sub-circuits are 2-bit and 3-bit counters with periods 4 and 8 that first
+        // activate after 3 and 7 presses, so they align at 7 presses (not at their lcm)
+        assert_eq!(part2(SAMPLES[2]), 7);
+
+        let mut modules = parse(SAMPLES[2]);
+        let rx = modules.id("rx").unwrap();
+        let first_low = (1..)
+            .find(|_| {
+                modules
+                    .press()
+                    .iter()
+                    .any(|record| record.to == rx && record.pulse == utils::Pulse::Low)
+            })
+            .unwrap();
+        assert_eq!(first_low, 7);
+    }
+
+    #[test]
+    pub fn press_history() {
+        let mut modules = parse(SAMPLES[0]);
+        let history = modules.press();
+        assert_eq!(history.len(), 12);
+        assert_eq!(
+            history
+                .iter()
+                .filter(|record| record.pulse == utils::Pulse::High)
+                .count(),
+            4
+        );
+        assert_eq!(history[0].from, None);
+        assert_eq!(modules.name(history[1].to), "a");
+    }
+
+    #[test]
+    pub fn cycle_analysis() {
+        let modules = parse(SAMPLES[2]);
+        let sub_circuits = modules.sub_circuits("rx").unwrap();
+        assert_eq!(
+            sub_circuits
+                .iter()
+                .map(|x| x
+                    .modules
+                    .iter()
+                    .map(|&id| modules.name(id))
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            [
+                vec!["a1", "a2", "ca", "ia"],
+                vec!["b1", "b2", "b3", "cb", "ib"]
+            ]
+        );
+        assert_eq!(
+            modules
+                .cycles("rx", 100)
+                .unwrap()
+                .iter()
+                .map(|cycle| (cycle.first, cycle.period))
+                .collect::<Vec<_>>(),
+            [(3, 4), (7, 8)]
+        );
+        assert!(matches!(
+            modules.cycles("rx", 10),
+            Err(utils::Error::NoCycle(_))
+        ));
+        assert!(matches!(
+            parse(SAMPLES[1]).presses_until_low("output", 100),
+            Err(utils::Error::UnknownModule(_))
+        ));
+        assert!(matches!(
+            parse(SAMPLES[1]).presses_until_low("outputs", 100),
+            Err(utils::Error::Unsupported(_))
+        ));
+    }
 }
@@ -18,22 +18,23 @@ fn parse(input: &str) -> utils::Brickfall {
 #[aoc(day22, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let input = parse(input);
-    (0..input.len())
-        .filter(|&i| input.bricks_affected_by(i).all(|x| !x))
+    let graph = parse(input).support_graph();
+    (0..graph.n_bricks())
+        .filter(|&i| graph.is_safe_to_disintegrate(i))
         .count()
 }
 
 #[aoc(day22, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    let input = parse(input);
-    (0..input.len())
-        .map(|i| input.bricks_affected_by(i).filter(|&x| x).count())
+    parse(input)
+        .support_graph()
+        .chain_reaction_sizes()
+        .into_iter()
         .sum()
 }
 
-mod utils {
+pub mod utils {
     use itertools::Itertools;
 
     pub struct Brick {
@@ -99,46 +100,135 @@ mod utils {
                 .into()
         }
 
+        /// Determines which bricks rest on which other bricks.
+        ///
         /// Must be called after `apply_gravity`.
-        pub fn bricks_affected_by(
-            &self,
-            analyzed_brick_index: usize,
-        ) -> impl Iterator<Item = bool> + '_ {
+        #[must_use]
+        pub fn support_graph(&self) -> SupportGraph {
+            let mut graph = petgraph::Graph::with_capacity(self.len() + 1, self.len());
+            let ground = graph.add_node(Support::Ground);
             let mut heightmap = rustc_hash::FxHashMap::default();
-            self.0
-                .iter()
-                .enumerate()
-                // Skip the analyzed brick to see what other bricks are affected by it
-                .filter_map(move |(i, brick)| (i != analyzed_brick_index).then_some(brick))
-                .map(move |brick| {
-                    // Find the highest point in the area below the brick
-                    let max_z = (brick.start.0..=brick.end.0)
-                        .cartesian_product(brick.start.1..=brick.end.1)
-                        .map(|position| heightmap.get(&position).copied().unwrap_or_default())
-                        .max()
-                        .unwrap_or_default();
+            for (i, brick) in self.iter().enumerate() {
+                let node = graph.add_node(Support::Brick(i));
+                (brick.start.0..=brick.end.0)
+                    .cartesian_product(brick.start.1..=brick.end.1)
+                    .filter_map(|position| {
+                        heightmap
+                            .insert(position, (brick.end.2, node))
+                            .filter(|&(z, _)| z + 1 == brick.start.2)
+                            .map(|(_, below)| below)
+                    })
+                    .unique()
+                    .collect::<smallvec::SmallVec<[_; 4]>>()
+                    .into_iter()
+                    .for_each(|below| {
+                        graph.add_edge(below, node, ());
+                    });
+                if brick.start.2 == 1 {
+                    graph.add_edge(ground, node, ());
+                }
+            }
+            SupportGraph { graph, ground }
+        }
+    }
 
-                    // Check if the brick is affected by the analyzed brick
-                    let delta_z = brick.start.2 - max_z - 1;
-                    let is_brick_affected = delta_z > 0;
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Support {
+        Ground,
+        Brick(usize),
+    }
 
-                    // Update the heightmap with either the original or updated height
-                    (brick.start.0..=brick.end.0)
-                        .cartesian_product(brick.start.1..=brick.end.1)
-                        .for_each(|position| {
-                            heightmap.insert(
-                                position,
-                                if is_brick_affected {
-                                    brick.end.2 - delta_z
-                                } else {
-                                    brick.end.2
-                                },
-                            );
-                        });
+    impl std::fmt::Display for Support {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Ground => write!(f, "ground"),
+                Self::Brick(i) => write!(f, "{i}"),
+            }
+        }
+    }
+
+    /// Directed graph from each brick (or the ground) to the bricks resting on it.
+    pub struct SupportGraph {
+        /// Node `i + 1` is brick `i`, nodes are in topological order.
+        graph: petgraph::Graph<Support, ()>,
+        ground: petgraph::graph::NodeIndex,
+    }
+
+    impl SupportGraph {
+        /// Number of bricks.
+        #[must_use]
+        pub fn n_bricks(&self) -> usize {
+            self.graph.node_count() - 1
+        }
+
+        /// Bricks that rest directly on the given brick.
+        pub fn supports(&self, brick: usize) -> impl Iterator<Item = usize> + '_ {
+            self.neighbors(brick, petgraph::Direction::Outgoing)
+        }
 
-                    is_brick_affected
+        /// Bricks that the given brick rests on directly.
+        pub fn supported_by(&self, brick: usize) -> impl Iterator<Item = usize> + '_ {
+            self.neighbors(brick, petgraph::Direction::Incoming)
+        }
+
+        fn neighbors(
+            &self,
+            brick: usize,
+            direction: petgraph::Direction,
+        ) -> impl Iterator<Item = usize> + '_ {
+            self.graph
+                .neighbors_directed(petgraph::graph::NodeIndex::new(brick + 1), direction)
+                .filter_map(|node| match self.graph[node] {
+                    Support::Ground => None,
+                    Support::Brick(i) => Some(i),
                 })
         }
+
+        /// Whether no other brick falls when the given brick is disintegrated.
+        #[must_use]
+        pub fn is_safe_to_disintegrate(&self, brick: usize) -> bool {
+            self.supports(brick).all(|above| {
+                self.graph
+                    .neighbors_directed(
+                        petgraph::graph::NodeIndex::new(above + 1),
+                        petgraph::Direction::Incoming,
+                    )
+                    .count()
+                    > 1
+            })
+        }
+
+        /// Number of other bricks that fall when each brick is disintegrated.
+        ///
+        /// A brick falls iff every path from the ground to it passes through the disintegrated
+        /// brick, i.e. iff it is dominated by the disintegrated brick. The chain reaction is
+        /// therefore the size of the brick's subtree in the dominator tree (without itself).
+        #[must_use]
+        pub fn chain_reaction_sizes(&self) -> Vec<usize> {
+            let dominators = petgraph::algo::dominators::simple_fast(&self.graph, self.ground);
+            // Accumulate the subtree sizes in reverse topological order
+            let mut subtree_sizes = vec![1; self.graph.node_count()];
+            for node in self.graph.node_indices().skip(1).rev() {
+                let parent = dominators.immediate_dominator(node).unwrap();
+                subtree_sizes[parent.index()] += subtree_sizes[node.index()];
+            }
+            subtree_sizes.into_iter().skip(1).map(|x| x - 1).collect()
+        }
+
+        /// Exports the graph in the DOT format of Graphviz.
+        #[must_use]
+        pub fn to_dot(&self) -> String {
+            use petgraph::dot::{Config, Dot};
+            format!(
+                "{:?}",
+                Dot::with_attr_getters(
+                    &self.graph,
+                    &[Config::EdgeNoLabel, Config::NodeNoLabel],
+                    &|_, _| String::new(),
+                    &|_, (_, support)| format!("label = \"{support}\""),
+                )
+            )
+        }
     }
 }
 
@@ -146,6 +236,7 @@ mod utils {
 mod tests {
     use super::*;
     use indoc::indoc;
+    use itertools::Itertools;
 
     const SAMPLE: &str = indoc! {"
         1,0,1~1,2,1
@@ -166,4 +257,19 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 7);
     }
+
+    #[test]
+    pub fn support_graph() {
+        let graph = parse(SAMPLE).support_graph();
+        assert_eq!(graph.supports(0).sorted().collect_vec(), [1, 2]);
+        assert_eq!(graph.supported_by(3).sorted().collect_vec(), [1, 2]);
+        assert_eq!(graph.supported_by(0).count(), 0);
+        assert_eq!(graph.chain_reaction_sizes(), [6, 0, 0, 0, 0, 1, 0]);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.contains("0 [ label = \"ground\"]"));
+        assert!(dot.contains("1 [ label = \"0\"]"));
+        assert!(dot.contains("1 -> 2 [ ]"));
+    }
 }
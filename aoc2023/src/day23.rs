@@ -91,46 +91,120 @@ mod utils {
         }
     }
 
-    type SuccessorLUT =
-        rustc_hash::FxHashMap<(usize, usize), rustc_hash::FxHashMap<(usize, usize), usize>>;
+    /// Graph of the junctions of the maze, connected by the corridors between them.
+    pub struct JunctionGraph {
+        pub positions: Vec<(usize, usize)>,
+        /// Outgoing corridors of each junction.
+        pub edges: Vec<smallvec::SmallVec<[Corridor; 4]>>,
+        pub start: usize,
+        pub end: usize,
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct Corridor {
+        pub to: usize,
+        /// Tiles of the corridor, excluding the junction it starts from.
+        pub tiles: Vec<(usize, usize)>,
+    }
+
+    impl Corridor {
+        #[must_use]
+        pub fn len(&self) -> usize {
+            self.tiles.len()
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Path {
+        pub length: usize,
+        /// Visited tiles, including the start and end position.
+        pub tiles: Vec<(usize, usize)>,
+    }
 
     impl Map {
         pub fn longest_path_len(&self, include_slopes: bool) -> usize {
-            // Construct LUT of successors
-            let mut successor_lut: SuccessorLUT = (0..self.matrix.rows)
+            self.longest_path(include_slopes, true).unwrap().length
+        }
+
+        /// Finds the longest path from the start to the end position that does not visit any
+        /// tile twice, optionally exploring the first branches of the search in parallel.
+        pub fn longest_path(&self, include_slopes: bool, parallel: bool) -> Option<Path> {
+            let graph = self.junction_graph(include_slopes);
+            let junctions = graph.longest_path(if parallel { PARALLEL_DEPTH } else { 0 })?;
+            let tiles = std::iter::once(graph.positions[graph.start])
+                .chain(junctions.windows(2).flat_map(|w| {
+                    graph.edges[w[0]]
+                        .iter()
+                        .filter(|corridor| corridor.to == w[1])
+                        .max_by_key(|corridor| corridor.len())
+                        .unwrap()
+                        .tiles
+                        .iter()
+                        .copied()
+                }))
+                .collect_vec();
+            Some(Path {
+                length: tiles.len() - 1,
+                tiles,
+            })
+        }
+
+        /// Compresses the maze into the graph of its junctions (tiles with at least three
+        /// neighbours) as well as the start and end position.
+        #[must_use]
+        pub fn junction_graph(&self, include_slopes: bool) -> JunctionGraph {
+            let positions = (0..self.matrix.rows)
                 .cartesian_product(0..self.matrix.columns)
-                .filter(|&position| self.matrix[position] != Tile::Forest)
-                .map(|position| {
-                    let successors = self
-                        .successors(position, include_slopes)
+                .filter(|&position| {
+                    self.matrix[position] != Tile::Forest
+                        && (position == self.start_position
+                            || position == self.end_position
+                            || self.successors(position, false).len() >= 3)
+                })
+                .collect_vec();
+            let ids = positions
+                .iter()
+                .enumerate()
+                .map(|(id, &position)| (position, id))
+                .collect::<rustc_hash::FxHashMap<_, _>>();
+
+            let edges = positions
+                .iter()
+                .map(|&junction| {
+                    self.successors(junction, include_slopes)
                         .into_iter()
-                        .map(|successor| (successor, 1))
-                        .collect();
-                    (position, successors)
+                        .filter_map(|next| {
+                            let (mut previous, mut position) = (junction, next);
+                            let mut tiles = vec![next];
+                            while !ids.contains_key(&position) {
+                                let successors = self.successors(position, include_slopes);
+                                // Dead ends and slopes against the direction of travel
+                                let &[next] = successors
+                                    .iter()
+                                    .filter(|&&next| next != previous)
+                                    .collect::<smallvec::SmallVec<[_; 4]>>()
+                                    .as_slice()
+                                else {
+                                    return None;
+                                };
+                                (previous, position) = (position, *next);
+                                tiles.push(position);
+                            }
+                            Some(Corridor {
+                                to: ids[&position],
+                                tiles,
+                            })
+                        })
+                        .collect()
                 })
                 .collect();
-            successor_lut
-                .clone()
-                .iter()
-                .filter(|(_, successors)| successors.len() == 2)
-                .map(|(&position, _)| position)
-                .for_each(|position| {
-                    let successors = successor_lut.remove(&position).unwrap();
-                    let ((position1, distance1), (position2, distance2)) =
-                        successors.iter().collect_tuple().unwrap();
-                    let position1_entry = successor_lut.get_mut(position1).unwrap();
-                    position1_entry.remove(&position);
-                    position1_entry.insert(*position2, distance1 + distance2);
-                    let position2_entry = successor_lut.get_mut(position2).unwrap();
-                    position2_entry.remove(&position);
-                    position2_entry.insert(*position1, distance1 + distance2);
-                });
-
-            // Find longest path with depth-first search
-            dfs_longest_path(self.start_position, &successor_lut, |&position| {
-                position == self.end_position
-            })
-            .unwrap()
+
+            JunctionGraph {
+                start: ids[&self.start_position],
+                end: ids[&self.end_position],
+                positions,
+                edges,
+            }
         }
 
         fn successors(
@@ -167,38 +241,166 @@ mod utils {
         }
     }
 
-    fn dfs_longest_path(
-        start: (usize, usize),
-        successor_lut: &SuccessorLUT,
-        mut success: impl FnMut(&(usize, usize)) -> bool,
-    ) -> Option<usize> {
-        fn step(
-            position: (usize, usize),
-            successor_lut: &SuccessorLUT,
-            visited: &mut std::collections::HashSet<(usize, usize)>,
-            success: &mut impl FnMut(&(usize, usize)) -> bool,
-        ) -> Option<usize> {
-            if success(&position) {
-                Some(0)
+    /// Number of levels of the search tree that are explored in parallel.
+    const PARALLEL_DEPTH: usize = 6;
+
+    /// Set of visited junctions, which fits into a single `u64` for most mazes.
+    trait Junctions: Clone + Send + Sync {
+        fn empty(n_junctions: usize) -> Self;
+        fn contains(&self, junction: usize) -> bool;
+        fn with(&self, junction: usize) -> Self;
+    }
+
+    impl Junctions for u64 {
+        fn empty(_: usize) -> Self {
+            0
+        }
+
+        fn contains(&self, junction: usize) -> bool {
+            self & (1 << junction) != 0
+        }
+
+        fn with(&self, junction: usize) -> Self {
+            self | (1 << junction)
+        }
+    }
+
+    impl Junctions for Vec<u64> {
+        fn empty(n_junctions: usize) -> Self {
+            vec![0; n_junctions.div_ceil(64)]
+        }
+
+        fn contains(&self, junction: usize) -> bool {
+            self[junction / 64] & (1 << (junction % 64)) != 0
+        }
+
+        fn with(&self, junction: usize) -> Self {
+            let mut visited = self.clone();
+            visited[junction / 64] |= 1 << (junction % 64);
+            visited
+        }
+    }
+
+    impl JunctionGraph {
+        /// Finds the junctions along the longest path from the start to the end junction,
+        /// exploring the first `parallel_depth` levels of the search in parallel.
+        #[must_use]
+        pub fn longest_path(&self, parallel_depth: usize) -> Option<Vec<usize>> {
+            // The exit is usually only reachable via a single junction, which means that the
+            // path must continue to the exit as soon as it enters that junction
+            let predecessors = (0..self.positions.len())
+                .filter(|&id| {
+                    self.edges[id]
+                        .iter()
+                        .any(|corridor| corridor.to == self.end)
+                })
+                .collect_vec();
+            let (target, bonus) = match predecessors.as_slice() {
+                &[id] if id != self.start => (id, self.max_len(id, self.end)),
+                _ => (self.end, 0),
+            };
+
+            // Every junction is entered at most once, which bounds the remaining length
+            let mut max_in = vec![0; self.positions.len()];
+            for corridor in self.edges.iter().flatten() {
+                max_in[corridor.to] = max_in[corridor.to].max(corridor.len());
+            }
+            max_in[self.start] = 0;
+            let remaining = max_in.iter().sum();
+
+            let search = Search {
+                graph: self,
+                target,
+                bonus,
+                max_in,
+                parallel_depth,
+                best: std::sync::atomic::AtomicUsize::new(0),
+            };
+            let n_junctions = self.positions.len();
+            let path = &mut vec![self.start];
+            let (_, mut path) = if n_junctions <= 64 {
+                let visited = u64::empty(n_junctions).with(self.start);
+                search.step(self.start, &visited, 0, remaining, path)
             } else {
-                let mut max_distance = None;
-                for (&n, offset) in successor_lut.get(&position).unwrap() {
-                    if visited.insert(n) {
-                        if let Some(distance) = step(n, successor_lut, visited, success) {
-                            max_distance = Some(max_distance.unwrap_or(0).max(distance + offset));
-                        }
-                        visited.remove(&n);
-                    }
-                }
-                max_distance
+                let visited = Vec::<u64>::empty(n_junctions).with(self.start);
+                search.step(self.start, &visited, 0, remaining, path)
+            }?;
+            if target != self.end {
+                path.push(self.end);
+            }
+            Some(path)
+        }
+
+        fn max_len(&self, from: usize, to: usize) -> usize {
+            self.edges[from]
+                .iter()
+                .filter(|corridor| corridor.to == to)
+                .map(Corridor::len)
+                .max()
+                .unwrap()
+        }
+    }
+
+    struct Search<'a> {
+        graph: &'a JunctionGraph,
+        target: usize,
+        bonus: usize,
+        /// Length of the longest corridor that enters each junction.
+        max_in: Vec<usize>,
+        parallel_depth: usize,
+        /// Length of the longest path found so far (by any thread).
+        best: std::sync::atomic::AtomicUsize,
+    }
+
+    impl Search<'_> {
+        /// Returns the longest path from the given junction if it improves on the best path
+        /// found so far.
+        fn step<V: Junctions>(
+            &self,
+            junction: usize,
+            visited: &V,
+            length: usize,
+            remaining: usize,
+            path: &mut Vec<usize>,
+        ) -> Option<(usize, Vec<usize>)> {
+            use rayon::prelude::*;
+            use std::sync::atomic::Ordering;
+
+            if junction == self.target {
+                let length = length + self.bonus;
+                return (self.best.fetch_max(length, Ordering::Relaxed) < length)
+                    .then(|| (length, path.clone()));
+            }
+            if length + remaining + self.bonus <= self.best.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            let corridors = self.graph.edges[junction]
+                .iter()
+                .filter(|corridor| !visited.contains(corridor.to));
+            let step = |corridor: &Corridor, path: &mut Vec<usize>| {
+                path.push(corridor.to);
+                let result = self.step(
+                    corridor.to,
+                    &visited.with(corridor.to),
+                    length + corridor.len(),
+                    remaining - self.max_in[corridor.to],
+                    path,
+                );
+                path.pop();
+                result
+            };
+            if path.len() <= self.parallel_depth {
+                corridors
+                    .collect::<smallvec::SmallVec<[_; 4]>>()
+                    .into_par_iter()
+                    .filter_map(|corridor| step(corridor, &mut path.clone()))
+                    .max_by_key(|&(length, _)| length)
+            } else {
+                // Every path that is returned improves on all previously returned paths
+                corridors.fold(None, |best, corridor| step(corridor, path).or(best))
             }
         }
-        step(
-            start,
-            successor_lut,
-            &mut std::collections::HashSet::new(),
-            &mut success,
-        )
     }
 }
 
@@ -242,4 +444,57 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 154);
     }
+
+    #[test]
+    pub fn longest_path() {
+        let map = parse(SAMPLE);
+        assert_eq!(map.junction_graph(false).positions.len(), 9);
+        for (include_slopes, length) in [(true, 94), (false, 154)] {
+            let path = map.longest_path(include_slopes, false).unwrap();
+            assert_eq!(
+                map.longest_path(include_slopes, true)
+                    .map(|path| path.length),
+                Some(length)
+            );
+            assert_eq!(path.length, length);
+            assert_eq!(path.tiles.len(), length + 1);
+            assert_eq!(path.tiles.first(), Some(&map.start_position));
+            assert_eq!(path.tiles.last(), Some(&map.end_position));
+            assert!(path
+                .tiles
+                .windows(2)
+                .all(|w| w[0].0.abs_diff(w[1].0) + w[0].1.abs_diff(w[1].1) == 1));
+            assert_eq!(
+                itertools::Itertools::unique(path.tiles.iter()).count(),
+                path.tiles.len()
+            );
+        }
+    }
+
+    #[test]
+    pub fn many_junctions() {
+        // A corridor with dead-end stubs, each of which turns a tile into a junction
+        let size = 141;
+        let mut rows = vec![vec!['#'; size]; size];
+        rows[0][1] = '.';
+        rows[1][1..size - 1].fill('.');
+        for x in (3..size - 3).step_by(2) {
+            rows[2][x] = '.';
+        }
+        for row in &mut rows[2..] {
+            row[size - 2] = '.';
+        }
+        let input = rows
+            .iter()
+            .map(|row| row.iter().chain(['\n'].iter()).collect::<String>())
+            .collect::<String>();
+        let map = parse(&input);
+        assert_eq!(map.junction_graph(false).positions.len(), 70);
+        for parallel in [false, true] {
+            assert_eq!(
+                map.longest_path(false, parallel).map(|path| path.length),
+                Some(2 * size - 4)
+            );
+        }
+    }
 }
@@ -16,7 +16,7 @@ fn parse(input: &str) -> utils::Hail {
                 .map(|s| s.trim().parse().unwrap())
                 .collect_tuple()
                 .unwrap();
-            utils::Hailstone { position, velocity }
+            geometry::Hailstone { position, velocity }
         })
         .collect_vec()
         .into()
@@ -25,45 +25,52 @@ fn parse(input: &str) -> utils::Hail {
 #[aoc(day24, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let input = parse(input);
-    input.count_intersections((200_000_000_000_000, 400_000_000_000_000))
+    parse(input).count_intersections(&geometry::TestArea::square(
+        200_000_000_000_000..=400_000_000_000_000,
+    ))
+}
+
+#[aoc(day24, part2)]
+#[must_use]
+pub fn part2(input: &str) -> u64 {
+    let rock = parse(input).rock().unwrap();
+    u64::try_from(rock.position.0 + rock.position.1 + rock.position.2).unwrap()
 }
 
+/// Cross-check of [`part2`] that hands the original (non-linear) system to Z3.
+///
 /// # Note
 /// I did not come up with this solution myself but instead found
 /// the suggestion of using `z3` on r/adventofcode.
-#[aoc(day24, part2)]
+#[aoc(day24, part2, z3)]
 #[allow(clippy::similar_names)]
 #[cfg(feature = "z3")]
 #[must_use]
-pub fn part2(input: &str) -> u64 {
-    use z3::ast::Ast;
-
+pub fn part2_z3(input: &str) -> u64 {
     let input = parse(input);
 
-    let context = z3::Context::new(&z3::Config::default());
-    let solver = z3::Solver::new(&context);
+    let solver = z3::Solver::new();
 
-    let px = z3::ast::Int::new_const(&context, "px");
-    let py = z3::ast::Int::new_const(&context, "py");
-    let pz = z3::ast::Int::new_const(&context, "pz");
-    let vx = z3::ast::Int::new_const(&context, "vx");
-    let vy = z3::ast::Int::new_const(&context, "vy");
-    let vz = z3::ast::Int::new_const(&context, "vz");
+    let px = z3::ast::Int::new_const("px");
+    let py = z3::ast::Int::new_const("py");
+    let pz = z3::ast::Int::new_const("pz");
+    let vx = z3::ast::Int::new_const("vx");
+    let vy = z3::ast::Int::new_const("vy");
+    let vz = z3::ast::Int::new_const("vz");
 
     for (i, hs) in input.iter().enumerate() {
-        let t_i = z3::ast::Int::new_const(&context, format!("t{i}"));
+        let t_i = z3::ast::Int::new_const(format!("t{i}"));
 
-        let px_i = z3::ast::Int::from_i64(&context, i64::try_from(hs.position.0).unwrap());
-        let py_i = z3::ast::Int::from_i64(&context, i64::try_from(hs.position.1).unwrap());
-        let pz_i = z3::ast::Int::from_i64(&context, i64::try_from(hs.position.2).unwrap());
-        let vx_i = z3::ast::Int::from_i64(&context, i64::try_from(hs.velocity.0).unwrap());
-        let vy_i = z3::ast::Int::from_i64(&context, i64::try_from(hs.velocity.1).unwrap());
-        let vz_i = z3::ast::Int::from_i64(&context, i64::try_from(hs.velocity.2).unwrap());
+        let px_i = z3::ast::Int::from_i64(i64::try_from(hs.position.0).unwrap());
+        let py_i = z3::ast::Int::from_i64(i64::try_from(hs.position.1).unwrap());
+        let pz_i = z3::ast::Int::from_i64(i64::try_from(hs.position.2).unwrap());
+        let vx_i = z3::ast::Int::from_i64(i64::try_from(hs.velocity.0).unwrap());
+        let vy_i = z3::ast::Int::from_i64(i64::try_from(hs.velocity.1).unwrap());
+        let vz_i = z3::ast::Int::from_i64(i64::try_from(hs.velocity.2).unwrap());
 
-        solver.assert(&(px.clone() + t_i.clone() * vx.clone())._eq(&(px_i + t_i.clone() * vx_i)));
-        solver.assert(&(py.clone() + t_i.clone() * vy.clone())._eq(&(py_i + t_i.clone() * vy_i)));
-        solver.assert(&(pz.clone() + t_i.clone() * vz.clone())._eq(&(pz_i + t_i.clone() * vz_i)));
+        solver.assert((px.clone() + t_i.clone() * vx.clone()).eq(px_i + t_i.clone() * vx_i));
+        solver.assert((py.clone() + t_i.clone() * vy.clone()).eq(py_i + t_i.clone() * vy_i));
+        solver.assert((pz.clone() + t_i.clone() * vz.clone()).eq(pz_i + t_i.clone() * vz_i));
     }
 
     match solver.check() {
@@ -76,7 +83,9 @@ pub fn part2(input: &str) -> u64 {
 }
 
 mod utils {
+    use super::geometry::{cross, sub, Hailstone, TestArea};
     use itertools::Itertools;
+    use num::{BigRational, Zero};
 
     #[derive(derive_more::Deref)]
     pub struct Hail(Vec<Hailstone>);
@@ -88,63 +97,327 @@ mod utils {
     }
 
     impl Hail {
-        pub fn count_intersections(&self, workspace: (i128, i128)) -> usize {
-            let lines = self.iter().map(Hailstone::path_as_line).collect_vec();
+        /// Counts the pairs of hailstones whose paths cross inside the test area in the XY
+        /// plane (in the future).
+        pub fn count_intersections(&self, area: &TestArea) -> usize {
+            self.iter()
+                .tuple_combinations()
+                .filter(|(a, b)| a.crosses_xy_in(b, area))
+                .count()
+        }
+
+        /// Finds the rock that hits every hailstone when thrown from an integer position with
+        /// an integer velocity.
+        ///
+        /// A rock `(P, V)` hits hailstone `(p, v)` iff `(P - p) x (V - v) = 0`. The non-linear
+        /// term `P x V` is shared by all hailstones, so subtracting the equations of two
+        /// hailstones `i` and `j` leaves the linear equations
+        /// `P x (v_j - v_i) + (p_j - p_i) x V = p_j x v_j - p_i x v_i`.
+        /// Two pairs give a 6x6 system, which is solved exactly and verified against all
+        /// hailstones.
+        pub fn rock(&self) -> Option<Hailstone> {
             (0..self.len())
-                .flat_map(|i| (i + 1..self.len()).map(move |j| (i, j)))
-                .filter(|&(i, j)| {
-                    lines[i].intersect(&lines[j]).is_some_and(|intersect| {
-                        (intersect.0 - self[i].position.0).signum() == (self[i].velocity.0).signum()
-                            && (intersect.0 - self[j].position.0).signum()
-                                == (self[j].velocity.0).signum()
-                            && (intersect.1 - self[i].position.1).signum()
-                                == (self[i].velocity.1).signum()
-                            && (intersect.1 - self[j].position.1).signum()
-                                == (self[j].velocity.1).signum()
-                            && intersect.0 >= workspace.0
-                            && intersect.0 <= workspace.1
-                            && intersect.1 >= workspace.0
-                            && intersect.1 <= workspace.1
-                    })
+                .tuple_combinations()
+                .find_map(|(i, j, k)| {
+                    let mut system = self.pair_equations(i, j);
+                    system.extend(self.pair_equations(i, k));
+                    solve(system)
                 })
-                .count()
+                .and_then(|x| {
+                    let rock = Hailstone {
+                        position: (x[0], x[1], x[2]),
+                        velocity: (x[3], x[4], x[5]),
+                    };
+                    self.iter()
+                        .all(|hailstone| rock.collision_time(hailstone).is_some())
+                        .then_some(rock)
+                })
+        }
+
+        /// Equations `P x (v_j - v_i) + (p_j - p_i) x V = p_j x v_j - p_i x v_i` as rows
+        /// `[coefficients of P, coefficients of V, right-hand side]`.
+        fn pair_equations(&self, i: usize, j: usize) -> Vec<[i128; 7]> {
+            let (a, b) = (&self[i], &self[j]);
+            let dv = sub(b.velocity, a.velocity);
+            let dp = sub(b.position, a.position);
+            let rhs = sub(cross(b.position, b.velocity), cross(a.position, a.velocity));
+            // P x dv = (Py dvz - Pz dvy, Pz dvx - Px dvz, Px dvy - Py dvx)
+            // dp x V = (dpy Vz - dpz Vy, dpz Vx - dpx Vz, dpx Vy - dpy Vx)
+            vec![
+                [0, dv.2, -dv.1, 0, -dp.2, dp.1, rhs.0],
+                [-dv.2, 0, dv.0, dp.2, 0, -dp.0, rhs.1],
+                [dv.1, -dv.0, 0, -dp.1, dp.0, 0, rhs.2],
+            ]
+        }
+    }
+
+    /// Solves the square linear system with exact rational arithmetic via Gaussian
+    /// elimination.
+    ///
+    /// Returns `None` if the system is singular or the solution is not integral.
+    fn solve(system: Vec<[i128; 7]>) -> Option<[i128; 6]> {
+        // Intermediate values exceed i128 when positions are ~1e14, hence the big integers
+        let mut rows = system
+            .into_iter()
+            .map(|row| row.map(|x| BigRational::from_integer(x.into())))
+            .collect_vec();
+        let n = rows.len();
+        for column in 0..n {
+            let pivot = (column..n).find(|&row| !rows[row][column].is_zero())?;
+            rows.swap(column, pivot);
+            let pivot = rows[column].clone();
+            for (i, row) in rows.iter_mut().enumerate() {
+                if i != column && !row[column].is_zero() {
+                    let factor = &row[column] / &pivot[column];
+                    for (x, p) in row.iter_mut().zip(&pivot).skip(column) {
+                        *x -= &factor * p;
+                    }
+                }
+            }
+        }
+        let mut solution = [0; 6];
+        for (i, (x, row)) in solution.iter_mut().zip(&rows).enumerate() {
+            let value = &row[n] / &row[i];
+            if !value.is_integer() {
+                return None;
+            }
+            *x = i128::try_from(value.to_integer()).ok()?;
         }
+        Some(solution)
+    }
+}
+
+pub mod geometry {
+    use num::{rational::Ratio, Zero};
+    use std::ops::RangeInclusive;
+
+    pub type Vector = (i128, i128, i128);
+
+    #[must_use]
+    pub fn sub(a: Vector, b: Vector) -> Vector {
+        (a.0 - b.0, a.1 - b.1, a.2 - b.2)
+    }
+
+    #[must_use]
+    pub fn dot(a: Vector, b: Vector) -> i128 {
+        a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+    }
+
+    #[must_use]
+    pub fn cross(a: Vector, b: Vector) -> Vector {
+        (
+            a.1 * b.2 - a.2 * b.1,
+            a.2 * b.0 - a.0 * b.2,
+            a.0 * b.1 - a.1 * b.0,
+        )
+    }
+
+    /// Relative position of two lines (or paths of hailstones).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Relation {
+        Coincident,
+        Parallel,
+        Intersecting,
+        /// Neither parallel nor intersecting (only possible in 3D).
+        Skew,
     }
 
+    /// Moment at which two hailstones are closest to each other.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Approach {
+        pub time: Ratio<i128>,
+        pub distance_squared: Ratio<i128>,
+    }
+
+    /// Rectangle in the XY plane (bounds are inclusive).
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct TestArea {
+        pub x: RangeInclusive<i128>,
+        pub y: RangeInclusive<i128>,
+    }
+
+    impl TestArea {
+        /// Square test area that uses the same range for both axes.
+        #[must_use]
+        pub fn square(range: RangeInclusive<i128>) -> Self {
+            Self {
+                x: range.clone(),
+                y: range,
+            }
+        }
+
+        #[must_use]
+        pub fn contains(&self, (x, y): (Ratio<i128>, Ratio<i128>)) -> bool {
+            contains(&self.x, *x.numer(), *x.denom()) && contains(&self.y, *y.numer(), *y.denom())
+        }
+    }
+
+    /// Whether `numerator / denominator` lies in the range (the denominator must be positive).
+    fn contains(range: &RangeInclusive<i128>, numerator: i128, denominator: i128) -> bool {
+        range.start() * denominator <= numerator && numerator <= range.end() * denominator
+    }
+
+    /// Unreduced crossing point `(x / denominator, y / denominator)` with a positive
+    /// denominator, which avoids the gcd reductions of [`Ratio`] in hot loops.
+    #[derive(Clone, Copy, Debug)]
+    struct Crossing {
+        x: i128,
+        y: i128,
+        denominator: i128,
+    }
+
+    impl Crossing {
+        fn point(self) -> (Ratio<i128>, Ratio<i128>) {
+            (
+                Ratio::new(self.x, self.denominator),
+                Ratio::new(self.y, self.denominator),
+            )
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct Hailstone {
-        pub position: (i128, i128, i128),
-        pub velocity: (i128, i128, i128),
+        pub position: Vector,
+        pub velocity: Vector,
     }
 
     impl Hailstone {
+        #[must_use]
+        pub fn position_at(&self, time: i128) -> Vector {
+            (
+                self.position.0 + time * self.velocity.0,
+                self.position.1 + time * self.velocity.1,
+                self.position.2 + time * self.velocity.2,
+            )
+        }
+
+        /// Non-negative integer time at which both hailstones are at the same position.
+        #[must_use]
+        pub fn collision_time(&self, other: &Self) -> Option<i128> {
+            let dp = sub(other.position, self.position);
+            let dv = sub(self.velocity, other.velocity);
+            let t = [(dp.0, dv.0), (dp.1, dv.1), (dp.2, dv.2)]
+                .into_iter()
+                .find(|&(_, dv)| dv != 0)
+                .map_or(Some(0), |(dp, dv)| (dp % dv == 0).then(|| dp / dv))?;
+            (t >= 0 && dp.0 == t * dv.0 && dp.1 == t * dv.1 && dp.2 == t * dv.2).then_some(t)
+        }
+
+        /// Time (not before now) at which both hailstones are closest to each other in 3D.
+        #[must_use]
+        pub fn closest_approach(&self, other: &Self) -> Approach {
+            let dp = sub(other.position, self.position);
+            let dv = sub(other.velocity, self.velocity);
+            // The squared distance |dp + t dv|^2 is minimal at t = -dp.dv / |dv|^2
+            let speed_squared = dot(dv, dv);
+            if speed_squared == 0 || dot(dp, dv) >= 0 {
+                return Approach {
+                    time: Ratio::zero(),
+                    distance_squared: Ratio::from_integer(dot(dp, dp)),
+                };
+            }
+            // |dp + t dv|^2 = |dp|^2 - (dp.dv)^2 / |dv|^2 = |dp x dv|^2 / |dv|^2
+            let normal = cross(dp, dv);
+            Approach {
+                time: Ratio::new(-dot(dp, dv), speed_squared),
+                distance_squared: Ratio::new(dot(normal, normal), speed_squared),
+            }
+        }
+
+        /// Relative position of the paths of both hailstones in 3D (ignoring time).
+        #[must_use]
+        pub fn path_relation(&self, other: &Self) -> Relation {
+            let dp = sub(other.position, self.position);
+            let normal = cross(self.velocity, other.velocity);
+            if normal == (0, 0, 0) {
+                if cross(dp, self.velocity) == (0, 0, 0) {
+                    Relation::Coincident
+                } else {
+                    Relation::Parallel
+                }
+            } else if dot(dp, normal) == 0 {
+                Relation::Intersecting
+            } else {
+                Relation::Skew
+            }
+        }
+
+        /// Point at which the paths of both hailstones cross in the XY plane, provided that
+        /// both hailstones reach it in the future.
+        #[must_use]
+        pub fn future_crossing_xy(&self, other: &Self) -> Option<(Ratio<i128>, Ratio<i128>)> {
+            self.future_crossing(other).map(Crossing::point)
+        }
+
+        /// Whether the paths of both hailstones cross inside the test area in the XY plane,
+        /// with both hailstones reaching the crossing in the future.
+        #[must_use]
+        pub fn crosses_xy_in(&self, other: &Self, area: &TestArea) -> bool {
+            self.future_crossing(other).is_some_and(|crossing| {
+                contains(&area.x, crossing.x, crossing.denominator)
+                    && contains(&area.y, crossing.y, crossing.denominator)
+            })
+        }
+
+        fn future_crossing(&self, other: &Self) -> Option<Crossing> {
+            let crossing = self.path_as_line().crossing(&other.path_as_line())?;
+            let is_future = |hailstone: &Self| {
+                (crossing.x - hailstone.position.0 * crossing.denominator).signum()
+                    == hailstone.velocity.0.signum()
+                    && (crossing.y - hailstone.position.1 * crossing.denominator).signum()
+                        == hailstone.velocity.1.signum()
+            };
+            (is_future(self) && is_future(other)).then_some(crossing)
+        }
+
+        /// Path of the hailstone projected onto the XY plane.
+        #[must_use]
         pub fn path_as_line(&self) -> Line {
             Line {
                 a: self.velocity.1,
-                py1: -self.velocity.0,
+                b: -self.velocity.0,
                 c: self.velocity.0 * self.position.1 - self.velocity.1 * self.position.0,
             }
         }
     }
 
+    /// Line `a x + b y + c = 0` in the XY plane.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct Line {
         a: i128,
-        py1: i128,
+        b: i128,
         c: i128,
     }
 
     impl Line {
-        pub fn intersect(&self, other: &Self) -> Option<(i128, i128)> {
-            if (self.a * other.py1 - other.a * self.py1) == 0 {
-                None
+        #[must_use]
+        pub fn relation(&self, other: &Self) -> Relation {
+            if self.a * other.b != other.a * self.b {
+                Relation::Intersecting
+            } else if self.a * other.c == other.a * self.c && self.b * other.c == other.b * self.c {
+                Relation::Coincident
             } else {
-                Some((
-                    (self.py1 * other.c - other.py1 * self.c)
-                        / (self.a * other.py1 - other.a * self.py1),
-                    (self.c * other.a - other.c * self.a)
-                        / (self.a * other.py1 - other.a * self.py1),
-                ))
+                Relation::Parallel
             }
         }
+
+        /// Exact intersection of two lines that are neither parallel nor coincident.
+        #[must_use]
+        pub fn intersect(&self, other: &Self) -> Option<(Ratio<i128>, Ratio<i128>)> {
+            self.crossing(other).map(Crossing::point)
+        }
+
+        fn crossing(&self, other: &Self) -> Option<Crossing> {
+            let determinant = self.a * other.b - other.a * self.b;
+            (determinant != 0).then(|| {
+                let sign = determinant.signum();
+                Crossing {
+                    x: sign * (self.b * other.c - other.b * self.c),
+                    y: sign * (self.c * other.a - other.c * self.a),
+                    denominator: sign * determinant,
+                }
+            })
+        }
     }
 }
 
@@ -163,12 +436,84 @@ mod tests {
 
     #[test]
     pub fn part1_example() {
-        assert_eq!(parse(SAMPLE).count_intersections((7, 27)), 2);
+        assert_eq!(
+            parse(SAMPLE).count_intersections(&geometry::TestArea::square(7..=27)),
+            2
+        );
     }
 
     #[test]
-    #[cfg(feature = "z3")]
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 47);
     }
+
+    #[test]
+    pub fn rock_example() {
+        let hail = parse(SAMPLE);
+        let rock = hail.rock().unwrap();
+        assert_eq!(rock.position, (24, 13, 10));
+        assert_eq!(rock.velocity, (-3, 1, 2));
+        assert_eq!(
+            hail.iter()
+                .map(|hailstone| rock.collision_time(hailstone).unwrap())
+                .collect::<Vec<_>>(),
+            [5, 3, 4, 6, 1]
+        );
+    }
+
+    #[test]
+    pub fn geometry_example() {
+        use geometry::{Hailstone, Relation};
+        use num::rational::Ratio;
+
+        let hail = parse(SAMPLE);
+        assert_eq!(
+            hail[0].future_crossing_xy(&hail[1]),
+            Some((Ratio::new(43, 3), Ratio::new(46, 3)))
+        );
+        assert_eq!(hail[0].future_crossing_xy(&hail[4]), None);
+        assert!(hail[0].crosses_xy_in(&hail[1], &geometry::TestArea::square(7..=27)));
+        assert!(!hail[0].crosses_xy_in(&hail[1], &geometry::TestArea::square(15..=27)));
+        assert!(!hail[0].crosses_xy_in(&hail[4], &geometry::TestArea::square(7..=27)));
+        assert_eq!(
+            hail[1].path_as_line().relation(&hail[2].path_as_line()),
+            Relation::Parallel
+        );
+        assert_eq!(hail[0].path_relation(&hail[1]), Relation::Skew);
+
+        let rock = hail.rock().unwrap();
+        assert_eq!(rock.path_relation(&hail[0]), Relation::Intersecting);
+        let approach = rock.closest_approach(&hail[0]);
+        assert_eq!(approach.time, Ratio::from_integer(5));
+        assert_eq!(approach.distance_squared, Ratio::from_integer(0));
+        assert_eq!(rock.position_at(5), hail[0].position_at(5));
+
+        let a = Hailstone {
+            position: (0, 0, 0),
+            velocity: (1, 1, 0),
+        };
+        let b = Hailstone {
+            position: (2, 2, 0),
+            velocity: (2, 2, 0),
+        };
+        let c = Hailstone {
+            position: (0, 10, 3),
+            velocity: (1, -1, 0),
+        };
+        assert_eq!(a.path_relation(&b), Relation::Coincident);
+        assert_eq!(a.collision_time(&b), None);
+        assert_eq!(
+            a.closest_approach(&c),
+            geometry::Approach {
+                time: Ratio::from_integer(5),
+                distance_squared: Ratio::from_integer(9),
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "z3")]
+    pub fn part2_z3_example() {
+        assert_eq!(part2_z3(SAMPLE), part2(SAMPLE));
+    }
 }
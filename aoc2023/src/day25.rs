@@ -24,24 +24,199 @@ fn parse(input: &str) -> petgraph::Graph<String, (), petgraph::Undirected> {
 #[must_use]
 pub fn part1(input: &str) -> usize {
     let input = parse(input);
-    let min_cut_res: Result<_, ()> =
-        rustworkx_core::connectivity::stoer_wagner_min_cut(&input, |_| Ok(1));
-    let (min_cut, partition) = min_cut_res.unwrap().unwrap();
-    assert_eq!(min_cut, 3);
-
-    (input.node_count() - partition.len()) * partition.len()
+    let cut = utils::min_cut(&input, utils::Algorithm::MaxFlow).unwrap();
+    cut.component_sizes.0 * cut.component_sizes.1
 }
 
 // Note: Day 25 does not have a part 2
 
+pub mod utils {
+    use itertools::Itertools;
+
+    pub type Graph = petgraph::Graph<String, (), petgraph::Undirected>;
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum Algorithm {
+        #[default]
+        StoerWagner,
+        /// Minimum over the maximum flows from a fixed node to every other node (with unit
+        /// capacities), which is fast when the cut is small.
+        MaxFlow,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum CutError {
+        /// The graph has less than two nodes.
+        TooSmall,
+        /// Removing the cut edges does not separate the components.
+        NotDisconnecting,
+        /// Removing the cut edges leaves more than two components (the graph was already
+        /// disconnected).
+        TooManyComponents(usize),
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Cut {
+        /// Names of the nodes of the cut edges, sorted.
+        pub edges: Vec<(String, String)>,
+        pub component_sizes: (usize, usize),
+    }
+
+    /// Finds a minimum cut that splits the graph into two components.
+    pub fn min_cut(graph: &Graph, algorithm: Algorithm) -> Result<Cut, CutError> {
+        if graph.node_count() < 2 {
+            return Err(CutError::TooSmall);
+        }
+
+        // Nodes on the same side of the cut as the first node
+        let side = match algorithm {
+            Algorithm::StoerWagner => {
+                let result: Result<_, ()> =
+                    rustworkx_core::connectivity::stoer_wagner_min_cut(graph, |_| Ok(1));
+                let (_, partition) = result.unwrap().ok_or(CutError::TooSmall)?;
+                let mut side = vec![false; graph.node_count()];
+                partition.iter().for_each(|node| side[node.index()] = true);
+                if !side[0] {
+                    side.iter_mut().for_each(|x| *x = !*x);
+                }
+                side
+            }
+            Algorithm::MaxFlow => max_flow_side(graph),
+        };
+
+        let cut_edges = graph
+            .edge_indices()
+            .filter(|&edge| {
+                let (a, b) = graph.edge_endpoints(edge).unwrap();
+                side[a.index()] != side[b.index()]
+            })
+            .collect_vec();
+        // Every crossing edge is cut, so exactly two components means both sides are connected
+        match component_count(graph, &cut_edges.iter().copied().collect()) {
+            0 | 1 => return Err(CutError::NotDisconnecting),
+            2 => {}
+            n => return Err(CutError::TooManyComponents(n)),
+        }
+
+        let n_side = side.iter().filter(|&&x| x).count();
+        Ok(Cut {
+            edges: cut_edges
+                .iter()
+                .map(|&edge| {
+                    let (a, b) = graph.edge_endpoints(edge).unwrap();
+                    let (a, b) = (graph[a].clone(), graph[b].clone());
+                    if a < b {
+                        (a, b)
+                    } else {
+                        (b, a)
+                    }
+                })
+                .sorted()
+                .collect(),
+            component_sizes: (n_side, graph.node_count() - n_side),
+        })
+    }
+
+    /// Number of connected components after removing the cut edges.
+    fn component_count(
+        graph: &Graph,
+        cut_edges: &rustc_hash::FxHashSet<petgraph::graph::EdgeIndex>,
+    ) -> usize {
+        let mut visited = vec![false; graph.node_count()];
+        let mut count = 0;
+        for start in graph.node_indices() {
+            if visited[start.index()] {
+                continue;
+            }
+            count += 1;
+            visited[start.index()] = true;
+            let mut stack = vec![start];
+            while let Some(node) = stack.pop() {
+                for edge in graph.edges(node) {
+                    use petgraph::visit::EdgeRef;
+                    let next = if edge.source() == node {
+                        edge.target()
+                    } else {
+                        edge.source()
+                    };
+                    if !cut_edges.contains(&edge.id()) && !visited[next.index()] {
+                        visited[next.index()] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// Computes the maximum flow from the first node to every other node and returns the
+    /// source side of the smallest resulting cut.
+    fn max_flow_side(graph: &Graph) -> Vec<bool> {
+        let edges = graph
+            .edge_indices()
+            .map(|edge| {
+                let (a, b) = graph.edge_endpoints(edge).unwrap();
+                (a.index(), b.index())
+            })
+            .collect_vec();
+        let mut adjacency = vec![Vec::new(); graph.node_count()];
+        for (i, &(a, b)) in edges.iter().enumerate() {
+            adjacency[a].push((b, i));
+            adjacency[b].push((a, i));
+        }
+
+        let mut best: Option<(usize, Vec<bool>)> = None;
+        for sink in 1..graph.node_count() {
+            // Flow of +1 along an edge goes from its first to its second node
+            let mut flow = vec![0_i8; edges.len()];
+            let mut value = 0;
+            loop {
+                if best.as_ref().is_some_and(|&(best, _)| value >= best) {
+                    break;
+                }
+
+                // Breadth-first search in the residual graph
+                let mut parents = vec![None; graph.node_count()];
+                let mut visited = vec![false; graph.node_count()];
+                let mut queue = std::collections::VecDeque::from([0]);
+                visited[0] = true;
+                while let Some(node) = queue.pop_front() {
+                    if node == sink {
+                        break;
+                    }
+                    for &(next, edge) in &adjacency[node] {
+                        let direction = if edges[edge].0 == node { 1 } else { -1 };
+                        if !visited[next] && flow[edge] != direction {
+                            visited[next] = true;
+                            parents[next] = Some((node, edge, direction));
+                            queue.push_back(next);
+                        }
+                    }
+                }
+
+                if !visited[sink] {
+                    // The nodes reachable in the residual graph form a minimum cut
+                    best = Some((value, visited));
+                    break;
+                }
+                let mut node = sink;
+                while let Some((parent, edge, direction)) = parents[node] {
+                    flow[edge] += direction;
+                    node = parent;
+                }
+                value += 1;
+            }
+        }
+        best.unwrap().1
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use indoc::indoc;
 
-    #[test]
-    pub fn part1_example() {
-        const SAMPLE: &str = indoc! {"
+    const SAMPLE: &str = indoc! {"
             jqt: rhn xhk nvd
             rsh: frs pzl lsr
             xhk: hfx
@@ -57,6 +232,73 @@ mod tests {
             frs: qnr lhk lsr
         "};
 
+    #[test]
+    pub fn part1_example() {
         assert_eq!(part1(SAMPLE), 54);
     }
+
+    #[test]
+    pub fn min_cut() {
+        let graph = parse(SAMPLE);
+        for algorithm in [utils::Algorithm::StoerWagner, utils::Algorithm::MaxFlow] {
+            let cut = utils::min_cut(&graph, algorithm).unwrap();
+            assert_eq!(
+                cut.edges,
+                [("bvb", "cmg"), ("hfx", "pzl"), ("jqt", "nvd")]
+                    .map(|(a, b)| (a.to_owned(), b.to_owned()))
+            );
+            assert_eq!(cut.component_sizes.0 * cut.component_sizes.1, 54);
+        }
+    }
+
+    #[test]
+    pub fn other_cut_sizes() {
+        // Two triangles connected by a single edge
+        let graph = parse(indoc! {"
+            a: b c
+            b: c
+            c: d
+            d: e f
+            e: f
+        "});
+        for algorithm in [utils::Algorithm::StoerWagner, utils::Algorithm::MaxFlow] {
+            let cut = utils::min_cut(&graph, algorithm).unwrap();
+            assert_eq!(cut.edges, [("c".to_owned(), "d".to_owned())]);
+            assert_eq!(cut.component_sizes, (3, 3));
+        }
+        assert_eq!(
+            utils::min_cut(&parse("a: b\n"), utils::Algorithm::MaxFlow)
+                .unwrap()
+                .component_sizes,
+            (1, 1)
+        );
+        assert_eq!(
+            utils::min_cut(&utils::Graph::default(), utils::Algorithm::MaxFlow),
+            Err(utils::CutError::TooSmall)
+        );
+    }
+
+    #[test]
+    pub fn disconnected_graph() {
+        let graph = parse(indoc! {"
+            a: b
+            c: d
+            e: f
+        "});
+        for algorithm in [utils::Algorithm::StoerWagner, utils::Algorithm::MaxFlow] {
+            assert_eq!(
+                utils::min_cut(&graph, algorithm),
+                Err(utils::CutError::TooManyComponents(3))
+            );
+        }
+        let graph = parse(indoc! {"
+            a: b
+            c: d
+        "});
+        for algorithm in [utils::Algorithm::StoerWagner, utils::Algorithm::MaxFlow] {
+            let cut = utils::min_cut(&graph, algorithm).unwrap();
+            assert!(cut.edges.is_empty());
+            assert_eq!(cut.component_sizes, (2, 2));
+        }
+    }
 }
@@ -1,5 +1,7 @@
 use aoc_runner_derive::aoc;
 
+use crate::interval::{IntervalSet, RangeMap};
+
 fn parse(input: &str) -> utils::Almanac {
     use itertools::Itertools;
 
@@ -8,31 +10,24 @@ fn parse(input: &str) -> utils::Almanac {
         .next()
         .unwrap()
         .split(':')
-        .last()
+        .next_back()
         .unwrap()
         .split_ascii_whitespace()
         .map(|s| s.parse().unwrap())
         .collect();
     let mappings = input_caterogies
         .map(|category| {
-            category
-                .lines()
-                .skip(1)
-                .map(|line| {
-                    let (destination_start, source_start, range_length) = line
-                        .split_ascii_whitespace()
-                        .map(|s| s.parse().unwrap())
-                        .next_tuple()
-                        .unwrap();
-                    utils::RangeMap {
-                        from: std::ops::Range {
-                            start: source_start,
-                            end: source_start.checked_add(range_length).unwrap(),
-                        },
-                        to_start: destination_start,
-                    }
-                })
-                .collect()
+            RangeMap::from_pieces(category.lines().skip(1).map(|line| {
+                let (destination_start, source_start, range_length): (i64, i64, i64) = line
+                    .split_ascii_whitespace()
+                    .map(|s| s.parse().unwrap())
+                    .next_tuple()
+                    .unwrap();
+                (
+                    source_start..source_start.checked_add(range_length).unwrap(),
+                    destination_start,
+                )
+            }))
         })
         .collect();
     utils::Almanac { seeds, mappings }
@@ -41,119 +36,45 @@ fn parse(input: &str) -> utils::Almanac {
 #[aoc(day5, part1)]
 #[must_use]
 pub fn part1(input: &str) -> u64 {
-    let input = parse(input);
-    let utils::Almanac { seeds, mappings } = input;
-    seeds
+    let almanac = parse(input);
+    let seed_to_location = almanac.seed_to_location();
+    let location = almanac
+        .seeds
         .iter()
-        .map(|&seed| {
-            mappings.iter().fold(seed, |value, mapping| {
-                mapping
-                    .iter()
-                    .find(|&range_map| range_map.from.contains(&value))
-                    .map_or(value, |range_map| {
-                        value + range_map.to_start - range_map.from.start
-                    })
-            })
-        })
+        .map(|&seed| seed_to_location.apply(seed))
         .min()
-        .unwrap()
+        .unwrap();
+    location.try_into().unwrap()
 }
 
 #[aoc(day5, part2)]
 #[must_use]
 pub fn part2(input: &str) -> u64 {
-    let input = parse(input);
-    let utils::Almanac { seeds, mappings } = input;
-    seeds
+    let almanac = parse(input);
+    let seeds = almanac
+        .seeds
         .chunks(2)
         .map(|nums| nums[0]..(nums[0] + nums[1]))
-        .flat_map(|seed_range| {
-            mappings.iter().fold(
-                smallvec::SmallVec::<[_; 32]>::from_elem(seed_range, 1),
-                |seed_ranges, mappings| {
-                    let mut mapped_ranges = smallvec::SmallVec::new();
-                    let leftover_ranges =
-                        mappings.iter().fold(seed_ranges, |seed_ranges, mapping| {
-                            seed_ranges
-                                .into_iter()
-                                .flat_map(|seed_range| {
-                                    if seed_range.start >= mapping.from.end
-                                        || seed_range.end <= mapping.from.start
-                                    {
-                                        [seed_range, std::ops::Range::default()]
-                                    } else if seed_range.start <= mapping.from.start
-                                        && seed_range.end >= mapping.from.end
-                                    {
-                                        let mapped = mapping.to_start
-                                            ..mapping.to_start + mapping.from.end
-                                                - mapping.from.start;
-                                        if mapped.start < mapped.end {
-                                            mapped_ranges.push(mapped);
-                                        }
-
-                                        [
-                                            seed_range.start..mapping.from.start,
-                                            mapping.from.end..seed_range.end,
-                                        ]
-                                    } else if seed_range.start >= mapping.from.start
-                                        && seed_range.end <= mapping.from.end
-                                    {
-                                        let mapped = seed_range.start - mapping.from.start
-                                            + mapping.to_start
-                                            ..seed_range.end - mapping.from.start
-                                                + mapping.to_start;
-                                        if mapped.start < mapped.end {
-                                            mapped_ranges.push(mapped);
-                                        }
-
-                                        [std::ops::Range::default(), std::ops::Range::default()]
-                                    } else {
-                                        let mapped = seed_range.start.max(mapping.from.start)
-                                            - mapping.from.start
-                                            + mapping.to_start
-                                            ..seed_range.end.min(mapping.from.end)
-                                                - mapping.from.start
-                                                + mapping.to_start;
-                                        if mapped.start < mapped.end {
-                                            mapped_ranges.push(mapped);
-                                        }
-
-                                        let leftover_start =
-                                            if seed_range.start < mapping.from.start {
-                                                seed_range.start
-                                            } else {
-                                                mapping.from.end
-                                            };
-                                        let leftover_end = if seed_range.end > mapping.from.end {
-                                            seed_range.end
-                                        } else {
-                                            mapping.from.start
-                                        };
-                                        [leftover_start..leftover_end, std::ops::Range::default()]
-                                    }
-                                })
-                                .filter(|range| range.start < range.end)
-                                .collect()
-                        });
-                    mapped_ranges.extend(leftover_ranges);
-                    mapped_ranges
-                },
-            )
-        })
-        .map(|range| range.start)
-        .min()
-        .unwrap()
+        .collect::<IntervalSet>();
+    let location = almanac.seed_to_location().image(&seeds).min().unwrap();
+    location.try_into().unwrap()
 }
 
 mod utils {
+    use crate::interval::RangeMap;
+
     pub struct Almanac {
-        pub seeds: smallvec::SmallVec<[u64; 20]>,
-        pub mappings: smallvec::SmallVec<[Vec<RangeMap>; 7]>,
+        pub seeds: smallvec::SmallVec<[i64; 20]>,
+        pub mappings: smallvec::SmallVec<[RangeMap; 7]>,
     }
 
-    pub struct RangeMap {
-        pub from: std::ops::Range<u64>,
-        pub to_start: u64,
+    impl Almanac {
+        /// Composes all category mappings into a single map.
+        pub fn seed_to_location(&self) -> RangeMap {
+            self.mappings
+                .iter()
+                .fold(RangeMap::new(), |map, mapping| map.then(mapping))
+        }
     }
 }
 
@@ -207,4 +128,14 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 46);
     }
+
+    #[test]
+    pub fn seed_to_location() {
+        let map = parse(SAMPLE).seed_to_location();
+        assert_eq!(
+            [79, 14, 55, 13].map(|seed| map.apply(seed)),
+            [82, 43, 86, 35]
+        );
+        assert_eq!(map.image(&IntervalSet::from(82..83)).min(), Some(46));
+    }
 }
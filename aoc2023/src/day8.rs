@@ -30,86 +30,192 @@ fn parse(input: &str) -> utils::Network {
         map.insert(key, [left, right]);
     }
 
-    utils::Network { steps, map }
+    utils::Network::new(steps, &map).unwrap()
 }
 
 #[aoc(day8, part1)]
 #[must_use]
 pub fn part1(input: &str) -> u64 {
-    let input = parse(input);
-    // Begin at "AAA"
-    let mut current_node = "AAA";
-
-    // Keep looping until the end is reached
-    let mut step_counter = 0;
-    loop {
-        // Iterate over all steps
-        for &step in &input.steps {
-            step_counter += 1;
-
-            // Update the current node
-            current_node = input.map[current_node][step].as_str();
-
-            // Return once the end is reached
-            if current_node == "ZZZ" {
-                return step_counter;
-            }
-        }
-    }
+    parse(input)
+        .steps_until(|node| node == "AAA", |node| node == "ZZZ")
+        .unwrap()
 }
 
 #[aoc(day8, part2)]
+#[must_use]
 pub fn part2(input: &str) -> u64 {
-    let input = parse(input);
-    // Find all nodes that end with 'A'
-    let mut nodes: smallvec::SmallVec<[&str; 8]> = input
-        .map
-        .keys()
-        .filter(|k| k.ends_with('A'))
-        .map(String::as_str)
-        .collect();
+    parse(input)
+        .steps_until(|node| node.ends_with('A'), |node| node.ends_with('Z'))
+        .unwrap()
+}
 
-    // Keep track of the lowest common multiple of all paths
-    let mut lcs = 1;
-
-    // Keep looping until all paths are finished
-    let mut step_counter = 0;
-    let mut finished_paths = smallvec::SmallVec::<[usize; 2]>::new();
-    loop {
-        // Iterate over all steps
-        for &step in &input.steps {
-            step_counter += 1;
-
-            // Update all active paths
-            nodes.iter_mut().enumerate().for_each(|(i, node)| {
-                *node = input.map[*node][step].as_str();
-                if node.ends_with('Z') {
-                    finished_paths.push(i);
-                }
-            });
+pub mod utils {
+    use itertools::Itertools;
 
-            if !finished_paths.is_empty() {
-                // Update the lowest common multiple
-                lcs = num::integer::lcm(lcs, step_counter);
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Error {
+        UnknownNode(String),
+        /// No node satisfies the start predicate.
+        NoStart,
+        /// The ghosts are never at end nodes during the same step.
+        NoCommonStep,
+    }
 
-                // Return once all nodes are finished
-                if nodes.len() == finished_paths.len() {
-                    return lcs;
-                }
+    pub struct Network {
+        pub steps: Vec<usize>,
+        names: Vec<String>,
+        /// Left and right successors of each node.
+        successors: Vec<[usize; 2]>,
+    }
 
-                // Remove all finished nodes
-                while let Some(i) = finished_paths.pop() {
-                    nodes.swap_remove(i);
-                }
+    /// Steps at which a ghost is at an end node.
+    ///
+    /// The state of a ghost is its node and the index of the next instruction, so it enters a
+    /// cycle after at most `n_nodes * n_instructions` steps.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Ghost {
+        /// Number of steps before the ghost enters its cycle.
+        pub tail: usize,
+        /// Length of the cycle.
+        pub cycle: usize,
+        /// Hits before the ghost enters its cycle.
+        pub tail_hits: Vec<usize>,
+        /// Hits during the first pass of the cycle, which repeat every `cycle` steps (the
+        /// start does not count as a hit, so a cycle through the start records it at `cycle`).
+        pub cycle_hits: Vec<usize>,
+    }
+
+    impl Ghost {
+        #[must_use]
+        pub fn is_at_end(&self, step: usize) -> bool {
+            if step < self.tail {
+                self.tail_hits.contains(&step)
+            } else {
+                let residue = (step - self.tail) % self.cycle;
+                self.cycle_hits
+                    .iter()
+                    .any(|&hit| (hit - self.tail) % self.cycle == residue)
             }
         }
     }
-}
 
-mod utils {
-    pub struct Network {
-        pub steps: Vec<usize>,
-        pub map: rustc_hash::FxHashMap<String, [String; 2]>,
+    impl Network {
+        pub fn new(
+            steps: Vec<usize>,
+            map: &rustc_hash::FxHashMap<String, [String; 2]>,
+        ) -> Result<Self, Error> {
+            let names = map.keys().sorted().cloned().collect_vec();
+            let ids = names
+                .iter()
+                .enumerate()
+                .map(|(id, name)| (name.as_str(), id))
+                .collect::<rustc_hash::FxHashMap<_, _>>();
+            let id = |name: &String| {
+                ids.get(name.as_str())
+                    .copied()
+                    .ok_or_else(|| Error::UnknownNode(name.clone()))
+            };
+            let successors = names
+                .iter()
+                .map(|name| {
+                    let [left, right] = &map[name];
+                    Ok([id(left)?, id(right)?])
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Self {
+                steps,
+                names,
+                successors,
+            })
+        }
+
+        /// Follows the instructions from the given node until the state repeats.
+        pub fn ghost(&self, start: &str, is_end: impl Fn(&str) -> bool) -> Result<Ghost, Error> {
+            let mut node = self
+                .names
+                .iter()
+                .position(|name| name == start)
+                .ok_or_else(|| Error::UnknownNode(start.to_owned()))?;
+            let mut first_seen = vec![usize::MAX; self.names.len() * self.steps.len()];
+            let mut hits = Vec::new();
+            for step in 0.. {
+                let instruction = step % self.steps.len();
+                let state = node * self.steps.len() + instruction;
+                if first_seen[state] != usize::MAX {
+                    let tail = first_seen[state];
+                    if tail == 0 && is_end(&self.names[node]) {
+                        hits.push(step);
+                    }
+                    let split = hits.partition_point(|&hit| hit < tail);
+                    return Ok(Ghost {
+                        tail,
+                        cycle: step - tail,
+                        cycle_hits: hits.split_off(split),
+                        tail_hits: hits,
+                    });
+                }
+                first_seen[state] = step;
+                if step > 0 && is_end(&self.names[node]) {
+                    hits.push(step);
+                }
+                node = self.successors[node][self.steps[instruction]];
+            }
+            unreachable!()
+        }
+
+        /// Number of steps until all ghosts that start at the start nodes are at end nodes
+        /// simultaneously.
+        pub fn steps_until(
+            &self,
+            is_start: impl Fn(&str) -> bool,
+            is_end: impl Fn(&str) -> bool + Copy,
+        ) -> Result<u64, Error> {
+            let ghosts = self
+                .names
+                .iter()
+                .filter(|name| is_start(name))
+                .map(|name| self.ghost(name, is_end))
+                .collect::<Result<Vec<_>, _>>()?;
+            if ghosts.is_empty() {
+                return Err(Error::NoStart);
+            }
+
+            // Before all ghosts are in their cycles, check each step individually
+            let max_tail = ghosts.iter().map(|ghost| ghost.tail).max().unwrap();
+            if let Some(step) = (1..max_tail).find(|&step| ghosts.iter().all(|g| g.is_at_end(step)))
+            {
+                return Ok(step as u64);
+            }
+
+            // Afterwards, the hits within the cycles are combined one ghost at a time into the
+            // distinct residues modulo the lcm of the cycles, which bounds their number by the lcm
+            // instead of the product of the hit counts
+            let (residues, modulus) = ghosts.iter().fold(
+                (vec![0], 1),
+                |(residues, modulus): (Vec<i128>, i128), ghost| {
+                    let cycle = ghost.cycle as i128;
+                    let residues = residues
+                        .iter()
+                        .cartesian_product(&ghost.cycle_hits)
+                        .filter_map(|(&residue, &hit)| {
+                            crate::modular::crt([(residue, modulus), (hit as i128, cycle)])
+                        })
+                        .map(|(residue, _)| residue)
+                        .sorted_unstable()
+                        .dedup()
+                        .collect();
+                    (residues, num::Integer::lcm(&modulus, &cycle))
+                },
+            );
+            residues
+                .into_iter()
+                .filter_map(|residue| {
+                    crate::modular::earliest_common([(residue, modulus)], max_tail.max(1) as i128)
+                })
+                .min()
+                .map(|step| u64::try_from(step).unwrap())
+                .ok_or(Error::NoCommonStep)
+        }
     }
 }
 
@@ -146,4 +252,125 @@ mod tests {
         "};
         assert_eq!(part2(SAMPLE), 6);
     }
+
+    #[test]
+    pub fn ghost_cycles() {
+        // The first ghost enters its cycle of length 2 after one step, the second ghost has a
+        // cycle of length 3, so the lcm of the first hits (2 and 1) is not a common step
+        let network = parse(indoc! {"
+            L
+
+            1A = (1B, XX)
+            1B = (1Z, XX)
+            1Z = (1B, XX)
+            2A = (2Z, XX)
+            2Z = (2B, XX)
+            2B = (2C, XX)
+            2C = (2Z, XX)
+            XX = (XX, XX)
+        "});
+        let is_end = |node: &str| node.ends_with('Z');
+        assert_eq!(
+            network.ghost("1A", is_end),
+            Ok(utils::Ghost {
+                tail: 1,
+                cycle: 2,
+                tail_hits: vec![],
+                cycle_hits: vec![2],
+            })
+        );
+        assert_eq!(
+            network.ghost("2A", is_end),
+            Ok(utils::Ghost {
+                tail: 1,
+                cycle: 3,
+                tail_hits: vec![],
+                cycle_hits: vec![1],
+            })
+        );
+        assert_eq!(
+            network.steps_until(|node| node.ends_with('A'), is_end),
+            Ok(4)
+        );
+        assert_eq!(
+            network.steps_until(|node| node == "2A", |node| node == "2C"),
+            Ok(3)
+        );
+        assert_eq!(
+            network.ghost("3A", is_end),
+            Err(utils::Error::UnknownNode("3A".to_owned()))
+        );
+        assert_eq!(
+            network.steps_until(|node| node.ends_with('Q'), is_end),
+            Err(utils::Error::NoStart)
+        );
+
+        // The cycle returns to the start, which is also the end
+        let network = parse(indoc! {"
+            L
+
+            A = (B, B)
+            B = (A, A)
+        "});
+        assert_eq!(
+            network.ghost("A", |node| node == "A"),
+            Ok(utils::Ghost {
+                tail: 0,
+                cycle: 2,
+                tail_hits: vec![],
+                cycle_hits: vec![2],
+            })
+        );
+        assert_eq!(
+            network.steps_until(|node| node == "A", |node| node == "A"),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    pub fn many_end_hits() {
+        // Every node is an end, so each ghost hits 6 ends per cycle (6^20 combinations of hits)
+        let nodes = (0..20)
+            .map(|i| format!("{i}A = ({i}B, {i}B)\n{i}B = ({i}C, {i}C)\n{i}C = ({i}A, {i}A)\n"))
+            .collect::<String>();
+        let network = parse(&format!("LR\n\n{nodes}"));
+        assert_eq!(
+            network.steps_until(|node| node.ends_with('A'), |_| true),
+            Ok(1)
+        );
+        assert_eq!(
+            network.steps_until(|node| node.ends_with('A'), |node| node.ends_with('A')),
+            Ok(3)
+        );
+    }
+
+    #[test]
+    pub fn unknown_node() {
+        let map = [("AAA".to_owned(), ["BBB".to_owned(), "AAA".to_owned()])]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            utils::Network::new(vec![0], &map).err(),
+            Some(utils::Error::UnknownNode("BBB".to_owned()))
+        );
+    }
+
+    #[test]
+    pub fn no_common_step() {
+        let network = parse(indoc! {"
+            L
+
+            1A = (1Z, XX)
+            1Z = (1B, XX)
+            1B = (1Z, XX)
+            2A = (2B, XX)
+            2B = (2Z, XX)
+            2Z = (2B, XX)
+            XX = (XX, XX)
+        "});
+        assert_eq!(
+            network.steps_until(|node| node.ends_with('A'), |node| node.ends_with('Z')),
+            Err(utils::Error::NoCommonStep)
+        );
+    }
 }
@@ -1,6 +1,6 @@
 use aoc_runner_derive::aoc;
 
-fn parse(input: &str) -> Vec<Vec<i32>> {
+fn parse(input: &str) -> Vec<Vec<i128>> {
     input
         .lines()
         .map(|line| {
@@ -13,39 +13,114 @@ fn parse(input: &str) -> Vec<Vec<i32>> {
 
 #[aoc(day9, part1)]
 #[must_use]
-pub fn part1(input: &str) -> i32 {
+pub fn part1(input: &str) -> i128 {
     let input = parse(input);
     input
         .iter()
-        .map(|seq| utils::predict_next_recursively(seq))
+        .map(|seq| utils::Polynomial::fit(seq).unwrap().next(1).unwrap())
         .sum()
 }
 
 #[aoc(day9, part2)]
 #[must_use]
-pub fn part2(input: &str) -> i32 {
+pub fn part2(input: &str) -> i128 {
     let input = parse(input);
     input
-        .clone()
-        .iter_mut()
-        .map(|arr| {
-            (*arr).reverse();
-            arr
-        })
-        .map(|seq| utils::predict_next_recursively(seq))
+        .iter()
+        .map(|seq| utils::Polynomial::fit(seq).unwrap().previous(1).unwrap())
         .sum()
 }
 
-mod utils {
-    pub fn predict_next_recursively(row: &[i32]) -> i32 {
-        let next_row: Vec<_> = row.windows(2).map(|w| w[1] - w[0]).collect();
+pub mod utils {
+    use num::traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, One, Zero};
 
-        row.last().unwrap()
-            + next_row
-                .iter()
-                .any(|&number| number != 0)
-                .then(|| predict_next_recursively(&next_row))
-                .unwrap_or(0)
+    /// Values that the polynomials can be evaluated with, e.g. `i128` or `num::BigInt`.
+    pub trait Value:
+        Clone + Zero + One + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + From<i64>
+    {
+    }
+
+    impl<T> Value for T where
+        T: Clone + Zero + One + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + From<i64>
+    {
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Overflow;
+
+    /// Polynomial through equidistant samples `f(0), f(1), ...` in Newton's forward
+    /// difference form `f(x) = sum_k Δ^k f(0) * binomial(x, k)`.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Polynomial<T> {
+        /// Leading forward differences `Δ^k f(0)` without trailing zeros.
+        differences: Vec<T>,
+        n_samples: usize,
+    }
+
+    impl<T: Value> Polynomial<T> {
+        pub fn fit(samples: &[T]) -> Result<Self, Overflow> {
+            let mut differences = Vec::with_capacity(samples.len());
+            let mut row = samples.to_vec();
+            while let Some(first) = row.first() {
+                differences.push(first.clone());
+                row = row
+                    .windows(2)
+                    .map(|w| w[1].checked_sub(&w[0]).ok_or(Overflow))
+                    .collect::<Result<_, _>>()?;
+            }
+            while differences.last().is_some_and(Zero::is_zero) {
+                differences.pop();
+            }
+            Ok(Self {
+                differences,
+                n_samples: samples.len(),
+            })
+        }
+
+        /// Degree of the polynomial (0 for the zero polynomial).
+        #[must_use]
+        pub fn degree(&self) -> usize {
+            self.differences.len().saturating_sub(1)
+        }
+
+        /// Whether the samples confirm the degree, i.e. whether at least one row of the
+        /// difference table is all zeros.
+        #[must_use]
+        pub fn is_determined(&self) -> bool {
+            self.n_samples > self.differences.len()
+        }
+
+        /// Evaluates the polynomial at `x`, where the samples are at `0..n_samples`.
+        pub fn evaluate(&self, x: i64) -> Result<T, Overflow> {
+            let mut value = T::zero();
+            let mut binomial = T::one();
+            for (k, difference) in self.differences.iter().enumerate() {
+                if k > 0 {
+                    // binomial(x, k) = binomial(x, k - 1) * (x - k + 1) / k (exact division)
+                    let k = i64::try_from(k).map_err(|_| Overflow)?;
+                    binomial = binomial
+                        .checked_mul(&T::from(x.checked_sub(k - 1).ok_or(Overflow)?))
+                        .and_then(|binomial| binomial.checked_div(&T::from(k)))
+                        .ok_or(Overflow)?;
+                }
+                value = difference
+                    .checked_mul(&binomial)
+                    .and_then(|term| value.checked_add(&term))
+                    .ok_or(Overflow)?;
+            }
+            Ok(value)
+        }
+
+        /// Extrapolates the given number of steps past the last sample.
+        pub fn next(&self, steps: i64) -> Result<T, Overflow> {
+            let last = i64::try_from(self.n_samples).map_err(|_| Overflow)? - 1;
+            self.evaluate(last.checked_add(steps).ok_or(Overflow)?)
+        }
+
+        /// Extrapolates the given number of steps before the first sample.
+        pub fn previous(&self, steps: i64) -> Result<T, Overflow> {
+            self.evaluate(steps.checked_neg().ok_or(Overflow)?)
+        }
     }
 }
 
@@ -69,4 +144,47 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 2);
     }
+
+    #[test]
+    pub fn polynomial_degree() {
+        let polynomials = parse(SAMPLE)
+            .iter()
+            .map(|seq| utils::Polynomial::fit(seq).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            polynomials
+                .iter()
+                .map(utils::Polynomial::degree)
+                .collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        assert!(polynomials.iter().all(utils::Polynomial::is_determined));
+        assert_eq!(polynomials[2].previous(1), Ok(5));
+        assert_eq!(polynomials[2].next(3), Ok(146));
+        assert_eq!(polynomials[1].evaluate(-3), Ok(1));
+        assert!(!utils::Polynomial::fit(&[1_i128, 2, 4])
+            .unwrap()
+            .is_determined());
+    }
+
+    #[test]
+    pub fn arbitrary_precision() {
+        // f(x) = x^10 exceeds i128 at x = 10^4
+        let samples = (0..12_i64).map(|x| x.pow(10)).collect::<Vec<_>>();
+        let small =
+            utils::Polynomial::fit(&samples.iter().map(|&x| i128::from(x)).collect::<Vec<_>>())
+                .unwrap();
+        assert_eq!(small.degree(), 10);
+        assert_eq!(small.evaluate(-5), Ok(5_i128.pow(10)));
+        assert_eq!(small.evaluate(10_000), Err(utils::Overflow));
+
+        let big = utils::Polynomial::fit(
+            &samples
+                .iter()
+                .map(|&x| num::BigInt::from(x))
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert_eq!(big.evaluate(10_000), Ok(num::BigInt::from(10).pow(40)));
+    }
 }
@@ -0,0 +1,289 @@
+//! Interval arithmetic shared by the puzzles that operate on ranges of integers.
+
+use std::ops::Range;
+
+/// Set of integers represented as sorted, disjoint and non-adjacent half-open ranges.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IntervalSet(Vec<Range<i64>>);
+
+impl IntervalSet {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn full() -> Self {
+        Self::from(i64::MIN..i64::MAX)
+    }
+
+    #[must_use]
+    pub fn ranges(&self) -> &[Range<i64>] {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Number of integers in the set.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.0
+            .iter()
+            .map(|range| range.end.abs_diff(range.start))
+            .sum()
+    }
+
+    #[must_use]
+    pub fn min(&self) -> Option<i64> {
+        self.0.first().map(|range| range.start)
+    }
+
+    #[must_use]
+    pub fn contains(&self, value: i64) -> bool {
+        let i = self.0.partition_point(|range| range.end <= value);
+        self.0.get(i).is_some_and(|range| range.contains(&value))
+    }
+
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        self.0.iter().chain(&other.0).cloned().collect()
+    }
+
+    #[must_use]
+    pub fn intersection(&self, other: &Self) -> Self {
+        let (mut i, mut j) = (0, 0);
+        let mut ranges = Vec::new();
+        while i < self.0.len() && j < other.0.len() {
+            let (a, b) = (&self.0[i], &other.0[j]);
+            let (start, end) = (a.start.max(b.start), a.end.min(b.end));
+            if start < end {
+                ranges.push(start..end);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self(ranges)
+    }
+
+    #[must_use]
+    pub fn difference(&self, other: &Self) -> Self {
+        self.intersection(&other.complement())
+    }
+
+    #[must_use]
+    pub fn complement(&self) -> Self {
+        let mut ranges = Vec::with_capacity(self.0.len() + 1);
+        let end = self.0.iter().fold(i64::MIN, |start, range| {
+            if start < range.start {
+                ranges.push(start..range.start);
+            }
+            range.end
+        });
+        if end < i64::MAX {
+            ranges.push(end..i64::MAX);
+        }
+        Self(ranges)
+    }
+}
+
+impl From<Range<i64>> for IntervalSet {
+    fn from(range: Range<i64>) -> Self {
+        std::iter::once(range).collect()
+    }
+}
+
+impl FromIterator<Range<i64>> for IntervalSet {
+    fn from_iter<I: IntoIterator<Item = Range<i64>>>(iter: I) -> Self {
+        let mut ranges = iter
+            .into_iter()
+            .filter(|range| range.start < range.end)
+            .collect::<Vec<_>>();
+        ranges.sort_unstable_by_key(|range| range.start);
+        let mut merged: Vec<Range<i64>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        Self(merged)
+    }
+}
+
+/// Piecewise-linear map that shifts each of its disjoint source ranges by an offset and maps
+/// every other integer onto itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RangeMap(Vec<(Range<i64>, i64)>);
+
+impl RangeMap {
+    /// Identity map.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a map from `(source, destination_start)` pairs with disjoint source ranges.
+    ///
+    /// # Panics
+    /// Panics if the source ranges overlap.
+    #[must_use]
+    pub fn from_pieces(pieces: impl IntoIterator<Item = (Range<i64>, i64)>) -> Self {
+        Self::from_offsets(
+            pieces
+                .into_iter()
+                .map(|(source, destination_start)| {
+                    let offset = destination_start - source.start;
+                    (source, offset)
+                })
+                .collect(),
+        )
+    }
+
+    fn from_offsets(mut pieces: Vec<(Range<i64>, i64)>) -> Self {
+        pieces.retain(|(range, offset)| range.start < range.end && *offset != 0);
+        pieces.sort_unstable_by_key(|(range, _)| range.start);
+        assert!(
+            pieces.windows(2).all(|w| w[0].0.end <= w[1].0.start),
+            "Source ranges must be disjoint"
+        );
+        // Merge adjacent pieces with the same offset
+        let mut merged: Vec<(Range<i64>, i64)> = Vec::with_capacity(pieces.len());
+        for (range, offset) in pieces {
+            match merged.last_mut() {
+                Some((last, last_offset)) if last.end == range.start && *last_offset == offset => {
+                    last.end = range.end;
+                }
+                _ => merged.push((range, offset)),
+            }
+        }
+        Self(merged)
+    }
+
+    /// Pieces of the map as `(source, offset)` pairs.
+    #[must_use]
+    pub fn pieces(&self) -> &[(Range<i64>, i64)] {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn apply(&self, value: i64) -> i64 {
+        let i = self.0.partition_point(|(range, _)| range.end <= value);
+        match self.0.get(i) {
+            Some((range, offset)) if range.contains(&value) => value + offset,
+            _ => value,
+        }
+    }
+
+    /// Maps every integer of the set.
+    #[must_use]
+    pub fn image(&self, set: &IntervalSet) -> IntervalSet {
+        self.segments()
+            .flat_map(|(range, offset)| {
+                set.intersection(&range.into())
+                    .0
+                    .into_iter()
+                    .map(move |range| range.start + offset..range.end + offset)
+            })
+            .collect()
+    }
+
+    /// Returns the composition that applies `self` first and `next` afterwards.
+    #[must_use]
+    pub fn then(&self, next: &Self) -> Self {
+        let pieces = self
+            .segments()
+            .flat_map(|(range, offset)| {
+                let image = range.start + offset..range.end + offset;
+                next.segments()
+                    .filter_map(move |(next_range, next_offset)| {
+                        let (start, end) = (
+                            image.start.max(next_range.start),
+                            image.end.min(next_range.end),
+                        );
+                        (start < end).then(|| (start - offset..end - offset, offset + next_offset))
+                    })
+            })
+            .collect();
+        Self::from_offsets(pieces)
+    }
+
+    /// Returns the inverse map, or `None` if the map is not a bijection.
+    #[must_use]
+    pub fn inverse(&self) -> Option<Self> {
+        let mut images = self
+            .segments()
+            .map(|(range, offset)| (range.start + offset..range.end + offset, -offset))
+            .collect::<Vec<_>>();
+        images.sort_unstable_by_key(|(range, _)| range.start);
+        let is_tiling = images
+            .first()
+            .is_some_and(|(range, _)| range.start == i64::MIN)
+            && images
+                .last()
+                .is_some_and(|(range, _)| range.end == i64::MAX)
+            && images.windows(2).all(|w| w[0].0.end == w[1].0.start);
+        is_tiling.then(|| Self::from_offsets(images))
+    }
+
+    /// Partitions the whole domain into the pieces and the identity gaps between them.
+    fn segments(&self) -> impl Iterator<Item = (Range<i64>, i64)> + '_ {
+        let gaps = IntervalSet(self.0.iter().map(|(range, _)| range.clone()).collect())
+            .complement()
+            .0
+            .into_iter()
+            .map(|range| (range, 0));
+        self.0.iter().cloned().chain(gaps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn set_operations() {
+        let a = IntervalSet::from_iter([0..5, 10..15, 4..7]);
+        let b = IntervalSet::from(3..12);
+        assert_eq!(a.ranges(), [0..7, 10..15]);
+        assert_eq!(a.len(), 12);
+        assert_eq!(a.union(&b), IntervalSet::from(0..15));
+        assert_eq!(a.intersection(&b).ranges(), [3..7, 10..12]);
+        assert_eq!(a.difference(&b).ranges(), [0..3, 12..15]);
+        assert!(a.contains(14) && !a.contains(8));
+    }
+
+    #[test]
+    pub fn map_composition() {
+        let f = RangeMap::from_pieces([(98..100, 50), (50..98, 52)]);
+        let g = RangeMap::from_pieces([(15..52, 0), (52..54, 37), (0..15, 39)]);
+        let h = f.then(&g);
+        for x in 0..120 {
+            assert_eq!(h.apply(x), g.apply(f.apply(x)));
+        }
+        assert_eq!(
+            h.image(&IntervalSet::from_iter([79..93, 55..68])),
+            IntervalSet::from_iter(
+                [79..93, 55..68]
+                    .into_iter()
+                    .flat_map(|r| r.map(|x| g.apply(f.apply(x))..g.apply(f.apply(x)) + 1))
+            )
+        );
+    }
+
+    #[test]
+    pub fn map_inverse() {
+        let f = RangeMap::from_pieces([(98..100, 50), (50..98, 52)]);
+        let inverse = f.inverse().unwrap();
+        for x in 0..120 {
+            assert_eq!(inverse.apply(f.apply(x)), x);
+        }
+        assert_eq!(f.then(&inverse), RangeMap::new());
+        assert_eq!(RangeMap::from_pieces([(0..10, 5)]).inverse(), None);
+    }
+}
@@ -26,4 +26,7 @@ pub mod day23;
 pub mod day24;
 pub mod day25;
 
+pub mod interval;
+pub mod modular;
+
 aoc_runner_derive::aoc_lib! { year = 2023 }
@@ -0,0 +1,46 @@
+//! Modular arithmetic shared by the puzzles that combine periodic events.
+
+/// Combines congruences `t = a (mod n)` into a single congruence `t = a (mod lcm(n...))` via
+/// the generalised Chinese remainder theorem (moduli do not need to be coprime).
+///
+/// Returns `None` if the congruences are incompatible.
+#[must_use]
+pub fn crt(congruences: impl IntoIterator<Item = (i128, i128)>) -> Option<(i128, i128)> {
+    use num_integer::Integer;
+
+    congruences
+        .into_iter()
+        .try_fold((0, 1), |(a1, n1): (i128, i128), (a2, n2)| {
+            let num_integer::ExtendedGcd { gcd, x, .. } = n1.extended_gcd(&n2);
+            if (a2 - a1) % gcd != 0 {
+                return None;
+            }
+            let lcm = n1 / gcd * n2;
+            let k = ((a2 - a1) / gcd * x).rem_euclid(n2 / gcd);
+            Some(((a1 + n1 * k).rem_euclid(lcm), lcm))
+        })
+}
+
+/// Returns the smallest `t >= min` that satisfies all congruences `t = a (mod n)`.
+#[must_use]
+pub fn earliest_common(
+    congruences: impl IntoIterator<Item = (i128, i128)>,
+    min: i128,
+) -> Option<i128> {
+    let (a, n) = crt(congruences)?;
+    Some(a + ((min - a).max(0) + n - 1) / n * n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn generalised_crt() {
+        assert_eq!(crt([(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        assert_eq!(crt([(3, 4), (7, 8)]), Some((7, 8)));
+        assert_eq!(crt([(1, 4), (2, 6)]), None);
+        assert_eq!(earliest_common([(0, 4), (0, 6)], 1), Some(12));
+        assert_eq!(earliest_common([(3, 4), (7, 8)], 7), Some(7));
+    }
+}
@@ -1,116 +1,246 @@
 use aoc_runner_derive::aoc;
-use itertools::Itertools;
 
 #[aoc(day10, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let (map, max_elevation) = Map::parse(input);
-
-    let mut goals: rustc_hash::FxHashMap<_, _> = max_elevation
+    Map::parse(input)
+        .trailheads(&TrailRules::default())
         .iter()
-        .map(|pos| {
-            (
-                *pos,
-                [pos.0 * map.ncols() + pos.1]
-                    .into_iter()
-                    .collect::<smallvec::SmallVec<[_; 8]>>(),
-            )
-        })
-        .collect();
-
-    (1..10).rev().for_each(|i| {
-        let mut next_goals = rustc_hash::FxHashMap::default();
-        for (&(x, y), goal) in &goals {
-            if x > 0 && map[(x - 1, y)] == i - 1 {
-                next_goals
-                    .entry((x - 1, y))
-                    .or_insert(smallvec::SmallVec::new())
-                    .extend(goal.clone());
-            }
-            if x < map.ncols() - 1 && map[(x + 1, y)] == i - 1 {
-                next_goals
-                    .entry((x + 1, y))
-                    .or_insert(smallvec::SmallVec::new())
-                    .extend(goal.clone());
-            }
-            if y > 0 && map[(x, y - 1)] == i - 1 {
-                next_goals
-                    .entry((x, y - 1))
-                    .or_insert(smallvec::SmallVec::new())
-                    .extend(goal.clone());
-            }
-            if y < map.nrows() - 1 && map[(x, y + 1)] == i - 1 {
-                next_goals
-                    .entry((x, y + 1))
-                    .or_insert(smallvec::SmallVec::new())
-                    .extend(goal.clone());
-            }
-        }
-        goals = next_goals;
-    });
-
-    goals
-        .values()
-        .map(|goal| goal.iter().unique().count())
+        .map(|trailhead| trailhead.summits.len())
         .sum()
 }
 
 #[aoc(day10, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    let (map, max_elevation) = Map::parse(input);
-
-    let mut goals: rustc_hash::FxHashMap<_, _> =
-        max_elevation.iter().map(|pos| (*pos, 1)).collect();
-
-    (1..10).rev().for_each(|i| {
-        let mut next_goals = rustc_hash::FxHashMap::default();
-        for (&(x, y), goal) in &goals {
-            if x > 0 && map[(x - 1, y)] == i - 1 {
-                *next_goals.entry((x - 1, y)).or_insert(0) += goal;
-            }
-
-            if x < map.ncols() - 1 && map[(x + 1, y)] == i - 1 {
-                *next_goals.entry((x + 1, y)).or_insert(0) += goal;
-            }
+    Map::parse(input)
+        .trailheads(&TrailRules::default())
+        .iter()
+        .map(|trailhead| trailhead.rating)
+        .sum()
+}
 
-            if y > 0 && map[(x, y - 1)] == i - 1 {
-                *next_goals.entry((x, y - 1)).or_insert(0) += goal;
-            }
+/// Rules that define hiking trails, which end at the first summit they reach.
+///
+/// Step rules that allow cycles (e.g. `±1`) are supported by enumerating the trails as
+/// simple paths, which takes exponential time in the worst case.
+pub struct TrailRules<F = fn(u8, u8) -> bool> {
+    /// Height of trailheads.
+    pub start: u8,
+    /// Height of summits.
+    pub end: u8,
+    /// Whether a step from the first height to the second height is allowed.
+    pub step: F,
+}
 
-            if y < map.nrows() - 1 && map[(x, y + 1)] == i - 1 {
-                *next_goals.entry((x, y + 1)).or_insert(0) += goal;
-            }
+impl Default for TrailRules {
+    fn default() -> Self {
+        Self {
+            start: 0,
+            end: 9,
+            step: |from, to| to == from + 1,
         }
-        goals = next_goals;
-    });
+    }
+}
 
-    goals.values().sum()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trailhead {
+    pub position: (usize, usize),
+    /// Distinct summits reachable from the trailhead, ordered by their position.
+    pub summits: Vec<(usize, usize)>,
+    /// Number of distinct hiking trails that start at the trailhead.
+    pub rating: usize,
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Reachable {
+    summits: Vec<(usize, usize)>,
+    n_trails: usize,
+}
+
+/// Heights of the map indexed by `(x, y)` (`None` for impassable tiles).
 #[repr(transparent)]
 #[derive(Clone, derive_more::Deref, derive_more::DerefMut)]
-struct Map(nalgebra::DMatrix<u8>);
+pub struct Map(nalgebra::DMatrix<Option<u8>>);
 
 impl Map {
-    fn parse(input: &str) -> (Self, smallvec::SmallVec<[(usize, usize); 256]>) {
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
         let input = input.as_bytes();
         let width = input.iter().position(|&c| c == b'\n').unwrap();
-        let mut map = nalgebra::DMatrix::from_element(width, (input.len() - 1) / width, b'0');
-        let mut max_elevation = smallvec::SmallVec::default();
+        let mut map = nalgebra::DMatrix::from_element(width, (input.len() - 1) / width, None);
         input.chunks(width + 1).enumerate().for_each(|(y, line)| {
-            line.iter().enumerate().for_each(|(x, &b)| {
-                if b.is_ascii_digit() {
-                    {
-                        map[(x, y)] = b - b'0';
-                        if b == b'9' {
-                            max_elevation.push((x, y));
-                        }
-                    }
-                }
+            line.iter().enumerate().take(width).for_each(|(x, &b)| {
+                map[(x, y)] = b.is_ascii_digit().then(|| b - b'0');
             });
         });
-        (Self(map), max_elevation)
+        Self(map)
+    }
+
+    /// Analyses all trailheads in row-major order.
+    pub fn trailheads<F: Fn(u8, u8) -> bool>(&self, rules: &TrailRules<F>) -> Vec<Trailhead> {
+        let mut memo = nalgebra::DMatrix::from_element(self.nrows(), self.ncols(), None);
+        let mut on_stack = nalgebra::DMatrix::from_element(self.nrows(), self.ncols(), false);
+        let trailheads = self
+            .positions_of(rules.start)
+            .map(|position| {
+                self.reachable(rules, position, &mut memo, &mut on_stack)
+                    .map(|reachable| Trailhead {
+                        position,
+                        summits: reachable.summits,
+                        rating: reachable.n_trails,
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>();
+        trailheads.unwrap_or_else(|_| {
+            // The step rule allows cycles, so trails must not revisit tiles
+            self.positions_of(rules.start)
+                .map(|position| {
+                    let mut summits = Vec::new();
+                    let mut rating = 0;
+                    self.simple_trails(rules, &mut vec![position], &mut |trail| {
+                        summits.push(*trail.last().unwrap());
+                        rating += 1;
+                    });
+                    summits.sort_unstable_by_key(|&(x, y)| (y, x));
+                    summits.dedup();
+                    Trailhead {
+                        position,
+                        summits,
+                        rating,
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// Lists all distinct hiking trails that start at the given position.
+    pub fn trails<F: Fn(u8, u8) -> bool>(
+        &self,
+        rules: &TrailRules<F>,
+        start: (usize, usize),
+    ) -> Vec<Vec<(usize, usize)>> {
+        let mut memo = nalgebra::DMatrix::from_element(self.nrows(), self.ncols(), None);
+        let mut on_stack = nalgebra::DMatrix::from_element(self.nrows(), self.ncols(), false);
+        let Ok(reachable) = self.reachable(rules, start, &mut memo, &mut on_stack) else {
+            let mut trails = Vec::new();
+            self.simple_trails(rules, &mut vec![start], &mut |trail| {
+                trails.push(trail.to_vec());
+            });
+            return trails;
+        };
+
+        let mut trails = Vec::with_capacity(reachable.n_trails);
+        let mut stack = vec![vec![start]];
+        while let Some(trail) = stack.pop() {
+            let position = *trail.last().unwrap();
+            if self[position] == Some(rules.end) {
+                trails.push(trail);
+                continue;
+            }
+            for next in self.steps(rules, position) {
+                if memo[next]
+                    .as_ref()
+                    .is_some_and(|reachable: &Reachable| reachable.n_trails > 0)
+                {
+                    let mut trail = trail.clone();
+                    trail.push(next);
+                    stack.push(trail);
+                }
+            }
+        }
+        trails.reverse();
+        trails
+    }
+
+    /// Calls `visit` for every trail that extends the given trail without revisiting a tile.
+    fn simple_trails<F: Fn(u8, u8) -> bool>(
+        &self,
+        rules: &TrailRules<F>,
+        trail: &mut Vec<(usize, usize)>,
+        visit: &mut impl FnMut(&[(usize, usize)]),
+    ) {
+        let position = *trail.last().unwrap();
+        if self[position] == Some(rules.end) {
+            visit(trail);
+            return;
+        }
+        for next in self
+            .steps(rules, position)
+            .collect::<smallvec::SmallVec<[_; 4]>>()
+        {
+            if !trail.contains(&next) {
+                trail.push(next);
+                self.simple_trails(rules, trail, visit);
+                trail.pop();
+            }
+        }
+    }
+
+    fn positions_of(&self, height: u8) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.ncols())
+            .flat_map(|y| (0..self.nrows()).map(move |x| (x, y)))
+            .filter(move |&position| self[position] == Some(height))
+    }
+
+    fn steps<'a, F: Fn(u8, u8) -> bool>(
+        &'a self,
+        rules: &'a TrailRules<F>,
+        (x, y): (usize, usize),
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let height = self[(x, y)];
+        [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ]
+        .into_iter()
+        .filter(move |&next| {
+            matches!(
+                (height, self.get(next).copied().flatten()),
+                (Some(from), Some(to)) if (rules.step)(from, to)
+            )
+        })
+    }
+
+    fn reachable<F: Fn(u8, u8) -> bool>(
+        &self,
+        rules: &TrailRules<F>,
+        position: (usize, usize),
+        memo: &mut nalgebra::DMatrix<Option<Reachable>>,
+        on_stack: &mut nalgebra::DMatrix<bool>,
+    ) -> Result<Reachable, &'static str> {
+        if let Some(reachable) = &memo[position] {
+            return Ok(reachable.clone());
+        }
+        if on_stack[position] {
+            return Err("The step rule allows cyclic trails");
+        }
+
+        let reachable = if self[position] == Some(rules.end) {
+            Reachable {
+                summits: vec![position],
+                n_trails: 1,
+            }
+        } else {
+            on_stack[position] = true;
+            let mut reachable = Reachable::default();
+            for next in self
+                .steps(rules, position)
+                .collect::<smallvec::SmallVec<[_; 4]>>()
+            {
+                let next = self.reachable(rules, next, memo, on_stack)?;
+                reachable.summits.extend(next.summits);
+                reachable.n_trails += next.n_trails;
+            }
+            on_stack[position] = false;
+            reachable.summits.sort_unstable_by_key(|&(x, y)| (y, x));
+            reachable.summits.dedup();
+            reachable
+        };
+        memo[position] = Some(reachable.clone());
+        Ok(reachable)
     }
 }
 
@@ -139,4 +269,114 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 81);
     }
+
+    #[test]
+    pub fn trailheads_example() {
+        let map = Map::parse(SAMPLE);
+        let trailheads = map.trailheads(&TrailRules::default());
+        assert_eq!(
+            trailheads
+                .iter()
+                .map(|trailhead| trailhead.summits.len())
+                .collect::<Vec<_>>(),
+            [5, 6, 5, 3, 1, 3, 5, 3, 5]
+        );
+        assert_eq!(
+            trailheads
+                .iter()
+                .map(|trailhead| trailhead.rating)
+                .collect::<Vec<_>>(),
+            [20, 24, 10, 4, 1, 4, 5, 8, 5]
+        );
+    }
+
+    #[test]
+    pub fn trails_example() {
+        let map = Map::parse(indoc! {"
+            .....0.
+            ..4321.
+            ..5..2.
+            ..6543.
+            ..7..4.
+            ..8765.
+            ..9....
+        "});
+        let trails = map.trails(&TrailRules::default(), (5, 0));
+        assert_eq!(trails.len(), 3);
+        assert!(trails.iter().all(|trail| trail.len() == 10));
+        assert_eq!(trails[0][..3], [(5, 0), (5, 1), (4, 1)]);
+    }
+
+    #[test]
+    pub fn custom_rules() {
+        let map = Map::parse(SAMPLE);
+        let rules = TrailRules {
+            start: 0,
+            end: 9,
+            step: |from: u8, to: u8| to > from && to - from <= 2,
+        };
+        let trailheads = map.trailheads(&rules);
+        assert!(trailheads.iter().map(|x| x.rating).sum::<usize>() > part2(SAMPLE));
+
+        let rules = TrailRules {
+            start: 0,
+            end: 9,
+            step: |from: u8, to: u8| from.abs_diff(to) == 1,
+        };
+        let trailheads = map.trailheads(&rules);
+        // Trails may now go up and down, so there are far more of them
+        assert_eq!(
+            trailheads
+                .iter()
+                .map(|trailhead| (trailhead.summits.len(), trailhead.rating))
+                .collect::<Vec<_>>(),
+            [
+                (6, 124),
+                (6, 112),
+                (6, 120),
+                (6, 224),
+                (1, 1),
+                (6, 224),
+                (6, 132),
+                (6, 224),
+                (6, 132)
+            ]
+        );
+    }
+
+    #[test]
+    pub fn cyclic_rules() {
+        let map = Map::parse(indoc! {"
+            012
+            101
+        "});
+        let rules = TrailRules {
+            start: 0,
+            end: 2,
+            step: |from: u8, to: u8| from.abs_diff(to) == 1,
+        };
+        assert_eq!(
+            map.trailheads(&rules),
+            [
+                Trailhead {
+                    position: (0, 0),
+                    summits: vec![(2, 0)],
+                    rating: 4,
+                },
+                Trailhead {
+                    position: (1, 1),
+                    summits: vec![(2, 0)],
+                    rating: 3,
+                },
+            ]
+        );
+        assert_eq!(
+            map.trails(&rules, (1, 1)),
+            [
+                vec![(1, 1), (0, 1), (0, 0), (1, 0), (2, 0)],
+                vec![(1, 1), (2, 1), (2, 0)],
+                vec![(1, 1), (1, 0), (2, 0)],
+            ]
+        );
+    }
 }
@@ -3,64 +3,122 @@ use aoc_runner_derive::aoc;
 #[aoc(day11, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    solve(&mut parse_input(input), 25)
+    count_stones(input, 25, &StandardRules::default()).unwrap()
 }
 
 #[aoc(day11, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    solve(&mut parse_input(input), 75)
+    count_stones(input, 75, &StandardRules::default()).unwrap()
 }
 
-fn parse_input(input: &str) -> rustc_hash::FxHashMap<usize, usize> {
-    input
-        .split_ascii_whitespace()
-        .map(|s| (s.parse().unwrap(), 1))
-        .collect()
+/// Counts the stones after `n_blinks` under the given rules.
+pub fn count_stones<C: Count>(
+    input: &str,
+    n_blinks: usize,
+    rules: &impl Rules,
+) -> Result<C, Overflow> {
+    (0..n_blinks)
+        .try_fold(Stones::<C>::parse(input), |stones, _| stones.blink(rules))
+        .map(|stones| stones.count())
 }
 
-fn solve(map: &mut rustc_hash::FxHashMap<usize, usize>, n_steps: u32) -> usize {
-    (0..n_steps).for_each(|_| {
-        map.clone()
-            .iter()
-            .filter(|(_, &value)| value != 0)
-            .for_each(|(&key, &value)| {
-                if key == 0 {
-                    add(map, 1, value);
-                    sub(map, key, value);
-                } else {
-                    let str_key = key.to_string();
-                    if str_key.len() % 2 == 0 {
-                        let (key0, key1) = {
-                            let key_len_half = str_key.len() / 2;
-                            (
-                                str_key[..key_len_half].parse().unwrap(),
-                                str_key[key_len_half..].parse().unwrap(),
-                            )
-                        };
-                        add(map, key0, value);
-                        add(map, key1, value);
-                        sub(map, key, value);
-                    } else {
-                        add(map, 2024 * key, value);
-                        sub(map, key, value);
-                    }
-                }
-            });
-    });
-    map.values().sum()
+/// Numeric type used for counting stones (e.g. `usize`, `u128` or a big integer).
+pub trait Count: Clone + Default + From<u8> + for<'a> std::ops::AddAssign<&'a Self> {}
+
+impl<C: Clone + Default + From<u8> + for<'a> std::ops::AddAssign<&'a C>> Count for C {}
+
+/// Error for a stone whose next number does not fit into a `u64`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overflow {
+    pub stone: u64,
+}
+
+/// Rule set that determines what a single stone turns into after a blink.
+pub trait Rules {
+    fn apply(&self, stone: u64) -> Result<smallvec::SmallVec<[u64; 2]>, Overflow>;
+}
+
+impl<F: Fn(u64) -> smallvec::SmallVec<[u64; 2]>> Rules for F {
+    fn apply(&self, stone: u64) -> Result<smallvec::SmallVec<[u64; 2]>, Overflow> {
+        Ok(self(stone))
+    }
+}
+
+/// Rules from the puzzle with a configurable multiplier.
+pub struct StandardRules {
+    pub multiplier: u64,
 }
 
-fn add(map: &mut rustc_hash::FxHashMap<usize, usize>, key: usize, value: usize) {
-    if let Some(key) = map.get_mut(&key) {
-        *key += value;
-    } else {
-        map.insert(key, value);
+impl Default for StandardRules {
+    fn default() -> Self {
+        Self { multiplier: 2024 }
     }
 }
 
-fn sub(map: &mut rustc_hash::FxHashMap<usize, usize>, key: usize, value: usize) {
-    *map.get_mut(&key).unwrap() -= value;
+impl Rules for StandardRules {
+    fn apply(&self, stone: u64) -> Result<smallvec::SmallVec<[u64; 2]>, Overflow> {
+        if stone == 0 {
+            return Ok(smallvec::smallvec![1]);
+        }
+        let n_digits = stone.ilog10() + 1;
+        if n_digits % 2 == 0 {
+            let divisor = 10_u64.pow(n_digits / 2);
+            Ok(smallvec::smallvec![stone / divisor, stone % divisor])
+        } else {
+            let next = stone
+                .checked_mul(self.multiplier)
+                .ok_or(Overflow { stone })?;
+            Ok(smallvec::smallvec![next])
+        }
+    }
+}
+
+/// Distribution of stones as counts per engraved number.
+#[derive(Clone, Debug, PartialEq, Eq, derive_more::Deref)]
+pub struct Stones<C = usize>(rustc_hash::FxHashMap<u64, C>);
+
+impl<C: Count> Stones<C> {
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
+        let mut stones = rustc_hash::FxHashMap::<u64, C>::default();
+        input
+            .split_ascii_whitespace()
+            .for_each(|s| *stones.entry(s.parse().unwrap()).or_default() += &C::from(1));
+        Self(stones)
+    }
+
+    pub fn blink(&self, rules: &impl Rules) -> Result<Self, Overflow> {
+        let mut stones = rustc_hash::FxHashMap::<u64, C>::default();
+        for (&stone, count) in &self.0 {
+            for next in rules.apply(stone)? {
+                *stones.entry(next).or_default() += count;
+            }
+        }
+        Ok(Self(stones))
+    }
+
+    /// Returns an iterator over the distributions after each consecutive blink, which ends
+    /// after the first overflow.
+    pub fn blinks<'a>(
+        &self,
+        rules: &'a impl Rules,
+    ) -> impl Iterator<Item = Result<Self, Overflow>> + 'a
+    where
+        C: 'a,
+    {
+        std::iter::successors(Some(self.blink(rules)), move |stones| {
+            stones.as_ref().ok().map(|stones| stones.blink(rules))
+        })
+    }
+
+    #[must_use]
+    pub fn count(&self) -> C {
+        self.0.values().fold(C::default(), |mut sum, count| {
+            sum += count;
+            sum
+        })
+    }
 }
 
 #[cfg(test)]
@@ -81,4 +139,65 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 65_601_038_650_482);
     }
+
+    #[test]
+    pub fn blink_distribution() {
+        let stones = Stones::<usize>::parse("0 1 10 99 999")
+            .blink(&StandardRules::default())
+            .unwrap();
+        assert_eq!(stones, Stones::parse("1 2024 1 0 9 9 2021976"));
+        assert_eq!(stones.count(), 7);
+    }
+
+    #[test]
+    pub fn per_step_counts() {
+        let counts: Vec<usize> = Stones::parse(SAMPLE)
+            .blinks(&StandardRules::default())
+            .take(6)
+            .map(|stones| stones.unwrap().count())
+            .collect();
+        assert_eq!(counts, [3, 4, 5, 9, 13, 22]);
+    }
+
+    #[test]
+    pub fn u128_counts() {
+        assert_eq!(
+            count_stones::<u128>(SAMPLE, 75, &StandardRules::default()),
+            Ok(65_601_038_650_482)
+        );
+    }
+
+    #[test]
+    pub fn custom_rules() {
+        let rules = |stone: u64| -> smallvec::SmallVec<[u64; 2]> {
+            if stone % 2 == 0 {
+                smallvec::smallvec![stone / 2, stone / 2]
+            } else {
+                smallvec::smallvec![stone + 1]
+            }
+        };
+        assert_eq!(count_stones::<usize>("3", 3, &rules), Ok(4));
+    }
+
+    #[test]
+    pub fn overflow() {
+        let rules = StandardRules {
+            multiplier: u64::MAX / 2,
+        };
+        assert_eq!(
+            count_stones::<usize>("3", 1, &rules),
+            Err(Overflow { stone: 3 })
+        );
+        let results = Stones::<usize>::parse("1")
+            .blinks(&rules)
+            .take(5)
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[1],
+            Err(Overflow {
+                stone: u64::MAX / 2
+            })
+        );
+    }
 }
@@ -5,51 +5,58 @@ use strum::IntoEnumIterator;
 #[aoc(day12, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let mut map = Map::parse(input);
-    let mut seen_pos = std::collections::HashSet::default();
-    (0..map.ncols())
-        .cartesian_product(0..map.nrows())
-        .filter_map(|pos| map.flood(&mut seen_pos, pos))
-        .map(|(area, perim)| area.len() * perim)
-        .sum()
+    Map::parse(input).regions().iter().map(Region::price).sum()
 }
 
 #[aoc(day12, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    let mut map = Map::parse(input);
-    let mut seen_pos = std::collections::HashSet::default();
-    (0..map.ncols())
-        .cartesian_product(0..map.nrows())
-        .filter_map(|x| map.flood(&mut seen_pos, x))
-        .map(|(area, _)| {
-            let mut n_corners = 0;
-            for &point in &area {
-                for a in Direction::iter() {
-                    let b = a.right();
-                    n_corners += usize::from(
-                        area.contains(&a.next(point))
-                            && area.contains(&b.next(point))
-                            && !area.contains(&b.next(a.next(point))),
-                    );
-                }
-                for a in Direction::iter() {
-                    n_corners += usize::from(
-                        !area.contains(&a.next(point)) && !area.contains(&a.right().next(point)),
-                    );
-                }
-            }
-            n_corners * area.len()
-        })
+    Map::parse(input)
+        .regions()
+        .iter()
+        .map(Region::bulk_price)
         .sum()
 }
 
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub plant: u8,
+    /// Cells of the region as `(x, y)` positions.
+    pub cells: Vec<(usize, usize)>,
+    pub perimeter: usize,
+    pub sides: usize,
+    /// Inclusive `(min, max)` corners of the bounding box.
+    pub bounding_box: ((usize, usize), (usize, usize)),
+    /// Number of holes, i.e. connected areas fully surrounded by this region.
+    pub n_holes: usize,
+    /// Indices of the regions that lie within the holes of this region.
+    pub enclosed_regions: Vec<usize>,
+}
+
+impl Region {
+    #[must_use]
+    pub fn area(&self) -> usize {
+        self.cells.len()
+    }
+
+    #[must_use]
+    pub fn price(&self) -> usize {
+        self.area() * self.perimeter
+    }
+
+    #[must_use]
+    pub fn bulk_price(&self) -> usize {
+        self.area() * self.sides
+    }
+}
+
 #[repr(transparent)]
 #[derive(Clone, derive_more::Deref, derive_more::DerefMut)]
-struct Map(nalgebra::DMatrix<u8>);
+pub struct Map(nalgebra::DMatrix<u8>);
 
 impl Map {
-    fn parse(input: &str) -> Self {
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
         let input = input.as_bytes();
         let width = input.iter().position(|&c| c == b'\n').unwrap();
         let mut map = nalgebra::DMatrix::from_element(width, (input.len() - 1) / width, b'0');
@@ -61,39 +68,124 @@ impl Map {
         Self(map)
     }
 
-    fn flood(
-        &mut self,
-        seen_pos: &mut rustc_hash::FxHashSet<(usize, usize)>,
-        start: (usize, usize),
-    ) -> Option<(rustc_hash::FxHashSet<(usize, usize)>, usize)> {
-        if !seen_pos.insert(start) {
-            return None;
+    /// Labels all connected regions of the same plant type in row-major order.
+    #[must_use]
+    pub fn regions(&self) -> Vec<Region> {
+        let mut labels = nalgebra::DMatrix::from_element(self.nrows(), self.ncols(), usize::MAX);
+        let mut regions = Vec::new();
+        for (y, x) in (0..self.ncols()).cartesian_product(0..self.nrows()) {
+            if labels[(x, y)] == usize::MAX {
+                let region = self.flood(&mut labels, (x, y), regions.len());
+                regions.push(region);
+            }
         }
+        for (id, region) in regions.iter_mut().enumerate() {
+            self.find_holes(&labels, id, region);
+        }
+        regions
+    }
 
-        let mut area = rustc_hash::FxHashSet::default();
-        area.insert(start);
-        let mut queue = Vec::new();
-        queue.push(start);
-        let plant = self.get(start).unwrap();
-        let mut perim = 0;
+    fn contains(&self, pos: (usize, usize)) -> bool {
+        pos.0 < self.nrows() && pos.1 < self.ncols()
+    }
+
+    fn flood(
+        &self,
+        labels: &mut nalgebra::DMatrix<usize>,
+        start: (usize, usize),
+        id: usize,
+    ) -> Region {
+        let plant = self[start];
+        labels[start] = id;
+        let mut cells = vec![start];
+        let mut queue = vec![start];
+        let mut perimeter = 0;
         while let Some(pos) = queue.pop() {
             for next in Direction::iter().map(|x| x.next(pos)) {
-                if next.0 >= self.ncols() || next.1 >= self.nrows() {
-                    perim += 1;
-                    continue;
+                if !self.contains(next) || self[next] != plant {
+                    perimeter += 1;
+                } else if labels[next] == usize::MAX {
+                    labels[next] = id;
+                    cells.push(next);
+                    queue.push(next);
                 }
-                if self.get(next).unwrap() == plant {
-                    if seen_pos.insert(next) {
-                        area.insert(next);
+            }
+        }
+
+        // The number of sides is equal to the number of corners
+        let in_region = |pos| labels.get(pos) == Some(&id);
+        let mut sides = 0;
+        for &point in &cells {
+            for a in Direction::iter() {
+                let b = a.right();
+                sides += usize::from(
+                    in_region(a.next(point))
+                        && in_region(b.next(point))
+                        && !in_region(b.next(a.next(point))),
+                );
+                sides += usize::from(!in_region(a.next(point)) && !in_region(b.next(point)));
+            }
+        }
+
+        let bounding_box = cells.iter().fold(
+            ((usize::MAX, usize::MAX), (0, 0)),
+            |((min_x, min_y), (max_x, max_y)), &(x, y)| {
+                ((min_x.min(x), min_y.min(y)), (max_x.max(x), max_y.max(y)))
+            },
+        );
+
+        Region {
+            plant,
+            cells,
+            perimeter,
+            sides,
+            bounding_box,
+            n_holes: 0,
+            enclosed_regions: Vec::new(),
+        }
+    }
+
+    fn find_holes(&self, labels: &nalgebra::DMatrix<usize>, id: usize, region: &mut Region) {
+        // Work within the bounding box extended by one cell on each side
+        let ((min_x, min_y), (max_x, max_y)) = region.bounding_box;
+        let (width, height) = (max_x - min_x + 3, max_y - min_y + 3);
+        let to_global =
+            |(x, y): (usize, usize)| ((x + min_x).wrapping_sub(1), (y + min_y).wrapping_sub(1));
+        let is_free = |pos| {
+            let global = to_global(pos);
+            !self.contains(global) || labels[global] != id
+        };
+        let flood = |visited: &mut nalgebra::DMatrix<bool>, start| {
+            visited[start] = true;
+            let mut cells = vec![start];
+            let mut queue = vec![start];
+            while let Some(pos) = queue.pop() {
+                for next in Direction::iter().map(|x| x.next(pos)) {
+                    if next.0 < width && next.1 < height && !visited[next] && is_free(next) {
+                        visited[next] = true;
+                        cells.push(next);
                         queue.push(next);
                     }
-                    continue;
                 }
-                perim += 1;
             }
-        }
+            cells
+        };
 
-        Some((area, perim))
+        // Flood the outside from the border, every remaining free cell belongs to a hole
+        let mut visited = nalgebra::DMatrix::from_element(width, height, false);
+        flood(&mut visited, (0, 0));
+        let mut enclosed = rustc_hash::FxHashSet::default();
+        for pos in (0..height).cartesian_product(0..width).map(|(y, x)| (x, y)) {
+            if !visited[pos] && is_free(pos) {
+                region.n_holes += 1;
+                enclosed.extend(
+                    flood(&mut visited, pos)
+                        .into_iter()
+                        .map(|pos| labels[to_global(pos)]),
+                );
+            }
+        }
+        region.enclosed_regions = enclosed.into_iter().sorted().collect();
     }
 }
 
@@ -192,4 +284,27 @@ mod tests {
         assert_eq!(part2(SAMPLE4), 236);
         assert_eq!(part2(SAMPLE5), 368);
     }
+
+    #[test]
+    pub fn region_analytics() {
+        let regions = Map::parse(SAMPLE1).regions();
+        assert_eq!(regions.len(), 5);
+        let c = regions.iter().find(|region| region.plant == b'C').unwrap();
+        assert_eq!((c.area(), c.perimeter, c.sides), (4, 10, 8));
+        assert_eq!(c.bounding_box, ((2, 1), (3, 3)));
+
+        let regions = Map::parse(SAMPLE2).regions();
+        assert_eq!(regions.len(), 5);
+        assert_eq!(
+            (regions[0].area(), regions[0].perimeter, regions[0].sides),
+            (21, 36, 20)
+        );
+        assert_eq!(regions[0].n_holes, 4);
+        assert_eq!(regions[0].enclosed_regions, [1, 2, 3, 4]);
+        assert!(regions[1..].iter().all(|x| x.n_holes == 0 && x.area() == 1));
+
+        let regions = Map::parse(SAMPLE5).regions();
+        assert_eq!(regions[0].n_holes, 2);
+        assert_eq!((regions[0].area(), regions[0].sides), (28, 12));
+    }
 }
@@ -4,42 +4,24 @@ use aoc_runner_derive::aoc;
 #[must_use]
 pub fn part1(input: &str) -> i32 {
     let mut problem = Environment::parse(input);
-    problem
-        .robots
-        .iter_mut()
-        .for_each(|x| x.step(problem.limits, 100));
+    problem.step(100);
     problem.safety_factor()
 }
 
 #[aoc(day14, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    let mut problem = Environment::parse(input);
-    for i in 0.. {
-        problem
-            .robots
-            .iter_mut()
-            .for_each(|x| x.step(problem.limits, 1));
-        if problem
-            .robots
-            .iter()
-            .map(|x| (x.pos - problem.limits / 2).abs().sum())
-            .sum::<i32>()
-            < 2_i32.pow(14)
-        {
-            return i + 1;
-        }
-    }
-    unreachable!()
+    Environment::parse(input).christmas_tree_time().unwrap()
 }
 
-struct Environment {
+pub struct Environment {
     robots: smallvec::SmallVec<[Robot; 512]>,
     limits: nalgebra::Vector2<i32>,
 }
 
 impl Environment {
-    fn parse(input: &str) -> Self {
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
         let robots: smallvec::SmallVec<[Robot; 512]> = input.lines().map(Robot::parse).collect();
         let limits = robots
             .iter()
@@ -52,6 +34,12 @@ impl Environment {
         Self { robots, limits }
     }
 
+    pub fn step(&mut self, dt: i32) {
+        self.robots
+            .iter_mut()
+            .for_each(|robot| robot.step(self.limits, dt));
+    }
+
     fn safety_factor(&self) -> i32 {
         let mut quad = [0; 4];
         for pos in self.robots.iter().map(|robot| robot.pos) {
@@ -63,6 +51,87 @@ impl Environment {
         }
         quad.iter().product()
     }
+
+    /// Returns the first second at which the robots are the most clustered along both axes.
+    ///
+    /// Positions along x repeat with period `width` and along y with period `height`, so the
+    /// second with the minimum variance is found independently for each axis and the two
+    /// residues are combined via the Chinese remainder theorem. `None` is returned if the
+    /// residues are incompatible (only possible for non-coprime dimensions).
+    #[must_use]
+    pub fn christmas_tree_time(&self) -> Option<usize> {
+        let (width, height) = (self.limits.x, self.limits.y);
+        let tx = self.min_variance_time(width, |robot| (robot.pos.x, robot.vel.x));
+        let ty = self.min_variance_time(height, |robot| (robot.pos.y, robot.vel.y));
+        crate::modular::crt([
+            (i128::from(tx), i128::from(width)),
+            (i128::from(ty), i128::from(height)),
+        ])
+        .map(|(t, _)| usize::try_from(t).unwrap())
+    }
+
+    fn min_variance_time(&self, period: i32, axis: impl Fn(&Robot) -> (i32, i32)) -> i32 {
+        (0..period)
+            .min_by_key(|&t| {
+                // Variance scaled by n^2 to stay in integer arithmetic
+                let (sum, sum_sq) = self
+                    .robots
+                    .iter()
+                    .map(|robot| {
+                        let (pos, vel) = axis(robot);
+                        i64::from((pos + t * vel).rem_euclid(period))
+                    })
+                    .fold((0, 0), |(sum, sum_sq), x| (sum + x, sum_sq + x * x));
+                self.robots.len() as i64 * sum_sq - sum * sum
+            })
+            .unwrap()
+    }
+
+    /// Renders the robot positions as ASCII art with `#` for occupied and `.` for empty tiles.
+    #[must_use]
+    pub fn render_ascii(&self) -> String {
+        self.occupancy()
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&occupied| if occupied { '#' } else { '.' })
+                    .chain(std::iter::once('\n'))
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    /// Renders the robot positions as a plain PBM (P1) image.
+    #[must_use]
+    pub fn render_pbm(&self) -> String {
+        let mut pbm = format!("P1\n{} {}\n", self.limits.x, self.limits.y);
+        for row in self.occupancy() {
+            pbm.push_str(
+                &row.iter()
+                    .map(|&occupied| if occupied { "1" } else { "0" })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+            pbm.push('\n');
+        }
+        pbm
+    }
+
+    fn occupancy(&self) -> Vec<Vec<bool>> {
+        let mut grid = vec![vec![false; self.limits.x as usize]; self.limits.y as usize];
+        for robot in &self.robots {
+            grid[robot.pos.y as usize][robot.pos.x as usize] = true;
+        }
+        grid
+    }
+}
+
+/// Renders the robot positions after the given number of seconds as ASCII art.
+#[must_use]
+pub fn render(input: &str, seconds: i32) -> String {
+    let mut problem = Environment::parse(input);
+    problem.step(seconds);
+    problem.render_ascii()
 }
 
 struct Robot {
@@ -116,6 +185,52 @@ mod tests {
 
     #[test]
     pub fn part2_example() {
-        assert_eq!(part2(SAMPLE), 1);
+        // The robots are most clustered at t = 2 (mod 11) along x and t = 3 (mod 7) along y
+        assert_eq!(part2(SAMPLE), 24);
+    }
+
+    #[test]
+    pub fn christmas_tree_time() {
+        // All robots meet at (5, 3) after 30 seconds
+        let input = indoc! {"
+            p=8,6 v=1,2
+            p=0,5 v=2,-1
+            p=7,1 v=-3,1
+            p=6,4 v=4,3
+            p=2,0 v=-1,-2
+            p=3,2 v=3,-3
+            p=10,1 v=-2,1
+            p=9,6 v=5,2
+        "};
+        assert_eq!(Environment::parse(input).christmas_tree_time(), Some(30));
+        assert_eq!(
+            render(input, 30),
+            indoc! {"
+                ...........
+                ...........
+                ...........
+                .....#.....
+                ...........
+                ...........
+                ...........
+            "}
+        );
+    }
+
+    #[test]
+    pub fn render_example() {
+        assert_eq!(
+            render(SAMPLE, 100),
+            indoc! {"
+                ......2..1.
+                ...........
+                1..........
+                .11........
+                .....1.....
+                ...12......
+                .1....1....
+            "}
+            .replace(|c: char| c.is_ascii_digit(), "#")
+        );
     }
 }
@@ -1,56 +1,142 @@
 use aoc_runner_derive::aoc;
 use rayon::prelude::*;
 
-fn parse(
-    input: &str,
-) -> (
-    impl Iterator<Item = &str> + Clone,
-    impl Iterator<Item = &str>,
-) {
+fn parse(input: &str) -> (TowelMatcher, impl Iterator<Item = &str> + Send) {
     let (available, required) = input.split_once("\n\n").unwrap();
-    (available.split(", "), required.lines())
+    (TowelMatcher::new(available.split(", ")), required.lines())
 }
 
 #[aoc(day19, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let (available, required) = parse(input);
+    let (matcher, required) = parse(input);
     required
         .par_bridge()
-        .filter(|towel| {
-            get_n_permutations(towel, &available, &mut rustc_hash::FxHashMap::default()) > 0
-        })
+        .filter(|design| matcher.is_possible(design))
         .count()
 }
 
 #[aoc(day19, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    let (available, required) = parse(input);
+    let (matcher, required) = parse(input);
     required
         .par_bridge()
-        .map(|towel| get_n_permutations(towel, &available, &mut rustc_hash::FxHashMap::default()))
+        .map(|design| matcher.count_arrangements(design))
         .sum()
 }
 
-fn get_n_permutations<'a>(
-    required: &'a str,
-    available: &(impl Iterator<Item = &'a str> + Clone),
-    cache: &mut rustc_hash::FxHashMap<&'a str, usize>,
-) -> usize {
-    if let Some(&c) = cache.get(required) {
-        return c;
+/// Trie of towel patterns that is reused across all designs.
+pub struct TowelMatcher {
+    /// Maps bytes to their index in the alphabet of the patterns (`u8::MAX` if absent).
+    alphabet: [u8; 256],
+    alphabet_len: usize,
+    /// Child node indices flattened with a stride of `alphabet_len` (`0` if absent).
+    children: Vec<u32>,
+    /// Pattern terminating at each node.
+    terminal: Vec<Option<Box<str>>>,
+}
+
+impl TowelMatcher {
+    #[must_use]
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str> + Clone) -> Self {
+        let mut alphabet = [u8::MAX; 256];
+        let mut alphabet_len = 0;
+        for b in patterns.clone().into_iter().flat_map(str::bytes) {
+            if alphabet[b as usize] == u8::MAX {
+                alphabet[b as usize] = u8::try_from(alphabet_len).unwrap();
+                alphabet_len += 1;
+            }
+        }
+
+        let mut matcher = Self {
+            alphabet,
+            alphabet_len,
+            children: vec![0; alphabet_len],
+            terminal: vec![None],
+        };
+        for pattern in patterns {
+            let node = pattern.bytes().fold(0, |node, b| {
+                let edge = node * alphabet_len + alphabet[b as usize] as usize;
+                if matcher.children[edge] == 0 {
+                    matcher.children[edge] = u32::try_from(matcher.terminal.len()).unwrap();
+                    matcher
+                        .children
+                        .extend(std::iter::repeat_n(0, alphabet_len));
+                    matcher.terminal.push(None);
+                }
+                matcher.children[edge] as usize
+            });
+            matcher.terminal[node] = Some(pattern.into());
+        }
+        matcher
     }
-    if required.is_empty() {
-        return 1;
+
+    /// Returns all patterns that match `design` at the given position.
+    pub fn matches_at<'a>(
+        &'a self,
+        design: &'a str,
+        start: usize,
+    ) -> impl Iterator<Item = &'a str> {
+        design.as_bytes()[start..]
+            .iter()
+            .scan(0, |node, &b| {
+                let symbol = self.alphabet[b as usize];
+                if symbol == u8::MAX {
+                    return None;
+                }
+                *node = self.children[*node * self.alphabet_len + symbol as usize] as usize;
+                (*node != 0).then_some(*node)
+            })
+            .filter_map(|node| self.terminal[node].as_deref())
+    }
+
+    #[must_use]
+    pub fn is_possible(&self, design: &str) -> bool {
+        self.count_arrangements(design) > 0
+    }
+
+    /// Counts the arrangements of patterns that form the design.
+    #[must_use]
+    pub fn count_arrangements(&self, design: &str) -> usize {
+        self.suffix_counts(design)[0]
+    }
+
+    /// Lazily enumerates all arrangements of patterns that form the design.
+    pub fn arrangements<'a>(&'a self, design: &'a str) -> impl Iterator<Item = Vec<&'a str>> {
+        let counts = self.suffix_counts(design);
+        let mut stack = vec![(0, Vec::new())];
+        std::iter::from_fn(move || {
+            while let Some((start, arrangement)) = stack.pop() {
+                if start == design.len() {
+                    return Some(arrangement);
+                }
+                let next = self
+                    .matches_at(design, start)
+                    .filter(|pattern| counts[start + pattern.len()] > 0)
+                    .collect::<Vec<_>>();
+                stack.extend(next.into_iter().rev().map(|pattern| {
+                    let mut arrangement = arrangement.clone();
+                    arrangement.push(pattern);
+                    (start + pattern.len(), arrangement)
+                }));
+            }
+            None
+        })
+    }
+
+    /// Number of arrangements for each suffix of the design, computed bottom-up.
+    fn suffix_counts(&self, design: &str) -> Vec<usize> {
+        let mut counts = vec![0; design.len() + 1];
+        counts[design.len()] = 1;
+        for start in (0..design.len()).rev() {
+            counts[start] = self
+                .matches_at(design, start)
+                .map(|pattern| counts[start + pattern.len()])
+                .sum();
+        }
+        counts
     }
-    let n_permutations = available
-        .clone()
-        .filter(|avail| avail.len() <= required.len() && required.starts_with(avail))
-        .map(|avail| get_n_permutations(&required[avail.len()..], available, cache))
-        .sum();
-    cache.insert(required, n_permutations);
-    n_permutations
 }
 
 #[cfg(test)]
@@ -80,4 +166,20 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 16);
     }
+
+    #[test]
+    pub fn arrangements_example() {
+        let (matcher, _) = parse(SAMPLE);
+        assert_eq!(
+            matcher.arrangements("gbbr").collect::<Vec<_>>(),
+            [
+                vec!["g", "b", "b", "r"],
+                vec!["g", "b", "br"],
+                vec!["gb", "b", "r"],
+                vec!["gb", "br"],
+            ]
+        );
+        assert_eq!(matcher.arrangements("rrbgbr").count(), 6);
+        assert_eq!(matcher.arrangements("ubwu").next(), None);
+    }
 }
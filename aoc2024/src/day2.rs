@@ -1,55 +1,116 @@
 use aoc_runner_derive::aoc;
 
 #[inline]
-fn parse_line(line: &str) -> smallvec::SmallVec<[u8; 8]> {
+fn parse_line(line: &str) -> smallvec::SmallVec<[i64; 8]> {
     line.split_ascii_whitespace()
         .map(|num| num.parse().unwrap())
         .collect()
 }
 
-#[inline]
-fn check_values(values: &[u8]) -> bool {
-    let mut inc = true;
-    let mut dec = true;
-    for win in values.windows(2) {
-        if win[0].abs_diff(win[1]) > 3 {
-            return false;
-        }
-        inc &= win[0] < win[1];
-        dec &= win[0] > win[1];
-        if !(inc ^ dec) {
-            return false;
-        }
-    }
-    true
-}
-
 #[aoc(day2, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
+    let checker = SafetyChecker::default();
     input
         .lines()
-        .filter(|line| check_values(&parse_line(line)))
+        .filter(|line| checker.is_safe(&parse_line(line)))
         .count()
 }
 
 #[aoc(day2, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
+    let checker = SafetyChecker {
+        tolerance: 1,
+        ..SafetyChecker::default()
+    };
     input
         .lines()
-        .filter(|line| {
-            let values = parse_line(line);
-            check_values(&values)
-                || (0..values.len()).any(|i| {
-                    let mut values = values.clone();
-                    values.remove(i);
-                    check_values(&values)
-                })
-        })
+        .filter(|line| checker.is_safe(&parse_line(line)))
         .count()
 }
 
+/// Report safety rule with a generalised Problem Dampener.
+#[derive(Clone, Debug)]
+pub struct SafetyChecker {
+    /// Allowed absolute difference between adjacent levels of a monotonic report.
+    pub steps: std::ops::RangeInclusive<i64>,
+    /// Maximum number of levels that may be removed.
+    pub tolerance: usize,
+}
+
+impl Default for SafetyChecker {
+    fn default() -> Self {
+        Self {
+            steps: 1..=3,
+            tolerance: 0,
+        }
+    }
+}
+
+impl SafetyChecker {
+    #[must_use]
+    pub fn is_safe(&self, levels: &[i64]) -> bool {
+        self.removals(levels).is_some()
+    }
+
+    /// Returns the fewest indices of levels whose removal makes the report safe, or `None` if
+    /// more than `tolerance` levels would have to be removed.
+    ///
+    /// Runs in `O(n * tolerance^2)` for a report with `n` levels.
+    #[must_use]
+    pub fn removals(&self, levels: &[i64]) -> Option<Vec<usize>> {
+        [1, -1]
+            .into_iter()
+            .filter_map(|sign| self.removals_monotonic(levels, sign))
+            .min_by_key(Vec::len)
+    }
+
+    fn removals_monotonic(&self, levels: &[i64], sign: i64) -> Option<Vec<usize>> {
+        let n = levels.len();
+        let k = self.tolerance;
+        if n == 0 {
+            return Some(Vec::new());
+        }
+
+        // `parent[i][r]` is set if level `i` can be kept as the last level with `r` levels
+        // removed so far, holding the index of the previously kept level (`None` if first)
+        let mut parent: Vec<smallvec::SmallVec<[Option<Option<usize>>; 4]>> =
+            vec![smallvec::smallvec![None; k + 1]; n];
+        for i in 0..n {
+            if i <= k {
+                parent[i][i] = Some(None);
+            }
+            for j in (i.saturating_sub(k + 1)..i).rev() {
+                let gap = i - 1 - j;
+                if !self.steps.contains(&(sign * (levels[i] - levels[j]))) {
+                    continue;
+                }
+                for r in gap..=k {
+                    if parent[i][r].is_none() && parent[j][r - gap].is_some() {
+                        parent[i][r] = Some(Some(j));
+                    }
+                }
+            }
+        }
+
+        // Pick the end state with the fewest removals in total and backtrack
+        let (mut i, mut r) = (n.saturating_sub(k + 1)..n)
+            .flat_map(|i| (0..=k).map(move |r| (i, r)))
+            .filter(|&(i, r)| r + n - 1 - i <= k && parent[i][r].is_some())
+            .min_by_key(|&(i, r)| r + n - 1 - i)?;
+        let mut removed = ((i + 1)..n).collect::<Vec<_>>();
+        while let Some(Some(j)) = parent[i][r] {
+            removed.extend(j + 1..i);
+            r -= i - j - 1;
+            i = j;
+        }
+        removed.extend(0..i);
+        removed.sort_unstable();
+        Some(removed)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,4 +134,27 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 4);
     }
+
+    #[test]
+    pub fn removals_example() {
+        let checker = SafetyChecker {
+            tolerance: 1,
+            ..SafetyChecker::default()
+        };
+        assert_eq!(checker.removals(&[7, 6, 4, 2, 1]), Some(vec![]));
+        assert_eq!(checker.removals(&[1, 2, 7, 8, 9]), None);
+        assert_eq!(checker.removals(&[1, 3, 2, 4, 5]), Some(vec![1]));
+        assert_eq!(checker.removals(&[8, 6, 4, 4, 1]), Some(vec![2]));
+    }
+
+    #[test]
+    pub fn removals_tolerance() {
+        let checker = SafetyChecker {
+            steps: 1..=2,
+            tolerance: 2,
+        };
+        assert_eq!(checker.removals(&[1, 9, 2, 0, 3, 4]), Some(vec![1, 3]));
+        assert_eq!(checker.removals(&[5, 1, 2, 3, 9, 9]), None);
+        assert_eq!(checker.removals(&[9, 1, 2, 3, 9]), Some(vec![0, 4]));
+    }
 }
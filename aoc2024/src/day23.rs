@@ -1,125 +1,149 @@
 use aoc_runner_derive::aoc;
 use itertools::Itertools;
 
-struct Graph {
-    nodes: rustc_hash::FxHashMap<usize, smallvec::SmallVec<[usize; 16]>>,
-    connection_map: [[bool; Self::SIZE]; Self::SIZE],
-}
-
-impl Graph {
-    const OFFSET: usize = (b'z' - b'a' + 1) as usize;
-    const SIZE: usize = Self::OFFSET * Self::OFFSET;
-
-    #[inline]
-    fn parse(input: &str) -> Self {
-        let mut connection_map = [[false; Self::SIZE]; Self::SIZE];
-        let nodes: rustc_hash::FxHashMap<_, smallvec::SmallVec<_>> = input
-            .as_bytes()
-            .chunks(6)
-            .fold(rustc_hash::FxHashMap::default(), |mut acc, connection| {
-                let nodes = unsafe {
-                    [
-                        Self::OFFSET
-                            .unchecked_mul((connection[0].unchecked_sub(b'a')) as usize)
-                            .unchecked_add((connection[1].unchecked_sub(b'a')) as usize),
-                        Self::OFFSET
-                            .unchecked_mul((connection[3].unchecked_sub(b'a')) as usize)
-                            .unchecked_add((connection[4].unchecked_sub(b'a')) as usize),
-                    ]
-                };
-                connection_map[nodes[0]][nodes[1]] = true;
-                connection_map[nodes[1]][nodes[0]] = true;
-                acc.entry(nodes[0]).or_default().push(nodes[1]);
-                acc.entry(nodes[1]).or_default().push(nodes[0]);
-                acc
-            });
-        Self {
-            nodes,
-            connection_map,
-        }
-    }
-}
-
 #[aoc(day23, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    const T_RANGE: std::ops::Range<usize> =
-        (Graph::OFFSET * (b't' - b'a') as usize)..(Graph::OFFSET * (b't' - b'a' + 1) as usize);
-    let graph = Graph::parse(input);
-    let mut visited = [false; Graph::SIZE];
-    T_RANGE
-        .filter_map(|node_a| {
-            if let Some(connected_nodes) = graph.nodes.get(&node_a) {
-                visited[node_a] = true;
-                Some(
-                    connected_nodes
-                        .iter()
-                        .enumerate()
-                        .map(|(i, &node_b)| {
-                            connected_nodes
-                                .iter()
-                                .skip(i)
-                                .filter(|&&node_c| {
-                                    graph.connection_map[node_b][node_c]
-                                        && !visited[node_b]
-                                        && !visited[node_c]
-                                })
-                                .count()
-                        })
-                        .sum::<usize>(),
-                )
-            } else {
-                None
-            }
-        })
-        .sum()
+    Graph::parse(input)
+        .triangles(|name| name.starts_with('t'))
+        .count()
 }
 
 #[aoc(day23, part2)]
 #[must_use]
 pub fn part2(input: &str) -> String {
-    const EXPECTED_LEN: usize = 14;
-    let graph = Graph::parse(input);
-    graph
-        .nodes
-        .into_iter()
-        .fold(
-            (
-                smallvec::SmallVec::<[_; EXPECTED_LEN]>::new(),
-                [false; Graph::SIZE],
-            ),
-            |(max, mut visited), (node_a, connected_nodes)| {
-                if visited[node_a] {
-                    return (max, visited);
-                }
-                let current = std::iter::once(node_a).chain(connected_nodes).fold(
-                    smallvec::SmallVec::<[_; EXPECTED_LEN]>::new(),
-                    |mut current, node_b| {
-                        if current.iter().all(|&c| graph.connection_map[node_b][c]) {
-                            visited[node_b] = true;
-                            current.push(node_b);
-                        }
-                        current
-                    },
-                );
-                if current.len() > max.len() {
-                    (current, visited)
-                } else {
-                    (max, visited)
-                }
-            },
-        )
-        .0
-        .into_iter()
-        .sorted_unstable()
-        .map(|node| {
-            format!(
-                "{}{}",
-                char::from(((node / Graph::OFFSET) as u8) + b'a'),
-                char::from(((node % Graph::OFFSET) as u8) + b'a')
-            )
-        })
-        .join(",")
+    Graph::parse(input).maximum_clique().join(",")
+}
+
+/// Undirected graph with interned node names.
+#[derive(Default)]
+pub struct Graph {
+    names: Vec<String>,
+    ids: rustc_hash::FxHashMap<String, usize>,
+    adjacency: Vec<rustc_hash::FxHashSet<usize>>,
+}
+
+impl Graph {
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
+        let mut graph = Self::default();
+        for line in input.lines() {
+            let (a, b) = line.split_once('-').unwrap();
+            graph.add_edge(a, b);
+        }
+        graph
+    }
+
+    pub fn add_edge(&mut self, a: &str, b: &str) {
+        let (a, b) = (self.intern(a), self.intern(b));
+        self.adjacency[a].insert(b);
+        self.adjacency[b].insert(a);
+    }
+
+    fn intern(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), id);
+        self.adjacency.push(rustc_hash::FxHashSet::default());
+        id
+    }
+
+    /// Enumerates all triangles that contain at least one node satisfying the predicate.
+    pub fn triangles<'a>(
+        &'a self,
+        predicate: impl Fn(&str) -> bool + 'a,
+    ) -> impl Iterator<Item = [&'a str; 3]> + 'a {
+        (0..self.names.len())
+            .flat_map(move |a| {
+                self.adjacency[a]
+                    .iter()
+                    .filter(move |&&b| b > a)
+                    .flat_map(move |&b| {
+                        self.adjacency[b]
+                            .iter()
+                            .filter(move |&&c| c > b && self.adjacency[a].contains(&c))
+                            .map(move |&c| [a, b, c])
+                    })
+            })
+            .map(|triangle| triangle.map(|id| self.names[id].as_str()))
+            .filter(move |triangle| triangle.iter().any(|name| predicate(name)))
+    }
+
+    /// Finds a maximum clique via Bron–Kerbosch with pivoting, returning its sorted node names.
+    #[must_use]
+    pub fn maximum_clique(&self) -> Vec<&str> {
+        let mut max = Vec::new();
+        self.bron_kerbosch(
+            &mut Vec::new(),
+            (0..self.names.len()).collect(),
+            Vec::new(),
+            &mut max,
+        );
+        max.into_iter()
+            .map(|id| self.names[id].as_str())
+            .sorted_unstable()
+            .collect()
+    }
+
+    fn bron_kerbosch(
+        &self,
+        clique: &mut Vec<usize>,
+        mut candidates: Vec<usize>,
+        mut excluded: Vec<usize>,
+        max: &mut Vec<usize>,
+    ) {
+        if candidates.is_empty() {
+            if excluded.is_empty() && clique.len() > max.len() {
+                max.clone_from(clique);
+            }
+            return;
+        }
+        // Prune branches that cannot lead to a larger clique
+        if clique.len() + candidates.len() <= max.len() {
+            return;
+        }
+
+        let pivot = candidates
+            .iter()
+            .chain(&excluded)
+            .max_by_key(|&&u| {
+                candidates
+                    .iter()
+                    .filter(|v| self.adjacency[u].contains(v))
+                    .count()
+            })
+            .copied()
+            .unwrap();
+        let branches = candidates
+            .iter()
+            .filter(|v| !self.adjacency[pivot].contains(v))
+            .copied()
+            .collect::<Vec<_>>();
+        for v in branches {
+            let neighbours = &self.adjacency[v];
+            clique.push(v);
+            self.bron_kerbosch(
+                clique,
+                candidates
+                    .iter()
+                    .filter(|u| neighbours.contains(u))
+                    .copied()
+                    .collect(),
+                excluded
+                    .iter()
+                    .filter(|u| neighbours.contains(u))
+                    .copied()
+                    .collect(),
+                max,
+            );
+            clique.pop();
+            candidates.retain(|&u| u != v);
+            excluded.push(v);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -171,4 +195,23 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), "co,de,ka,ta");
     }
+
+    #[test]
+    pub fn triangles_example() {
+        let graph = Graph::parse(SAMPLE);
+        assert_eq!(graph.triangles(|_| true).count(), 12);
+        assert_eq!(graph.triangles(|name| name == "wh").count(), 3);
+    }
+
+    #[test]
+    pub fn arbitrary_names() {
+        let graph = Graph::parse(indoc! {"
+            alpha-beta
+            beta-gamma
+            gamma-alpha
+            gamma-delta
+            delta-epsilon
+        "});
+        assert_eq!(graph.maximum_clique(), ["alpha", "beta", "gamma"]);
+    }
 }
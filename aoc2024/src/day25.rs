@@ -3,24 +3,134 @@ use aoc_runner_derive::aoc;
 #[aoc(day25, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let input = input
-        .split("\n\n")
-        .map(|block| {
-            block
-                .trim_ascii_end()
-                .bytes()
-                .fold(0, |acc, b| (b as usize & 1) | (acc << 1))
-        })
-        .collect::<smallvec::SmallVec<[_; 512]>>();
-    input
-        .iter()
-        .enumerate()
-        .map(|(i, k)| input.iter().skip(i + 1).filter(|&l| (k & l) == 0).count())
-        .sum()
+    Schematics::parse(input).unwrap().fitting_pairs().count()
 }
 
 // Note: Day 25 does not have a part 2
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Lock,
+    Key,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schematic {
+    pub kind: Kind,
+    /// Height of each column (pins for locks and teeth for keys).
+    pub heights: Vec<usize>,
+    /// Available space between the top and bottom rows.
+    pub space: usize,
+}
+
+impl Schematic {
+    pub fn parse(input: &str) -> Result<Self, &'static str> {
+        let rows = input.lines().map(str::as_bytes).collect::<Vec<_>>();
+        if rows.len() < 2 {
+            return Err("Schematic must have at least two rows");
+        }
+        let width = rows[0].len();
+        if rows.iter().any(|row| row.len() != width) {
+            return Err("Schematic rows must have the same width");
+        }
+        if rows
+            .iter()
+            .flat_map(|row| row.iter())
+            .any(|&b| b != b'#' && b != b'.')
+        {
+            return Err("Schematic must only contain '#' and '.'");
+        }
+
+        let (kind, rows) = match (rows[0], rows[rows.len() - 1]) {
+            (top, bottom)
+                if top.iter().all(|&b| b == b'#') && bottom.iter().all(|&b| b == b'.') =>
+            {
+                (Kind::Lock, rows)
+            }
+            (top, bottom)
+                if top.iter().all(|&b| b == b'.') && bottom.iter().all(|&b| b == b'#') =>
+            {
+                (Kind::Key, rows.into_iter().rev().collect())
+            }
+            _ => return Err("Schematic must be filled at either the top or the bottom"),
+        };
+
+        // Columns must be contiguous from the filled edge
+        let heights = (0..width)
+            .map(|x| {
+                let height = rows[1..].iter().take_while(|row| row[x] == b'#').count();
+                if rows[1 + height..].iter().all(|row| row[x] == b'.') {
+                    Ok(height)
+                } else {
+                    Err("Schematic columns must be contiguous")
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            kind,
+            heights,
+            space: rows.len() - 2,
+        })
+    }
+
+    /// Returns the columns in which the two schematics overlap (for diagnostics, see
+    /// [`Self::fits`] for the fast check).
+    #[must_use]
+    pub fn overlapping_columns(&self, other: &Self) -> Vec<usize> {
+        self.heights
+            .iter()
+            .zip(&other.heights)
+            .enumerate()
+            .filter(|(_, (&a, &b))| a + b > self.space)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[must_use]
+    pub fn fits(&self, other: &Self) -> bool {
+        self.kind != other.kind
+            && self.space == other.space
+            && self.heights.len() == other.heights.len()
+            && self
+                .heights
+                .iter()
+                .zip(&other.heights)
+                .all(|(a, b)| a + b <= self.space)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Schematics {
+    pub locks: Vec<Schematic>,
+    pub keys: Vec<Schematic>,
+}
+
+impl Schematics {
+    pub fn parse(input: &str) -> Result<Self, &'static str> {
+        let mut schematics = Self::default();
+        for block in input.split("\n\n") {
+            let schematic = Schematic::parse(block)?;
+            match schematic.kind {
+                Kind::Lock => schematics.locks.push(schematic),
+                Kind::Key => schematics.keys.push(schematic),
+            }
+        }
+        Ok(schematics)
+    }
+
+    /// Iterates over the indices of all lock/key pairs that fit together.
+    pub fn fitting_pairs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.locks.iter().enumerate().flat_map(move |(i, lock)| {
+            self.keys
+                .iter()
+                .enumerate()
+                .filter(move |(_, key)| lock.fits(key))
+                .map(move |(j, _)| (i, j))
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,4 +182,45 @@ mod tests {
     pub fn part1_example() {
         assert_eq!(part1(SAMPLE), 3);
     }
+
+    #[test]
+    pub fn schematics_example() {
+        let schematics = Schematics::parse(SAMPLE).unwrap();
+        assert_eq!(
+            schematics
+                .locks
+                .iter()
+                .map(|lock| lock.heights.clone())
+                .collect::<Vec<_>>(),
+            [[0, 5, 3, 4, 3], [1, 2, 0, 5, 3]]
+        );
+        assert_eq!(
+            schematics
+                .keys
+                .iter()
+                .map(|key| key.heights.clone())
+                .collect::<Vec<_>>(),
+            [[5, 0, 2, 1, 3], [4, 3, 4, 0, 2], [3, 0, 2, 0, 1]]
+        );
+        assert_eq!(
+            schematics.locks[0].overlapping_columns(&schematics.keys[0]),
+            [4]
+        );
+        assert_eq!(
+            schematics.locks[0].overlapping_columns(&schematics.keys[1]),
+            [1, 2]
+        );
+        assert_eq!(
+            schematics.fitting_pairs().collect::<Vec<_>>(),
+            [(0, 2), (1, 1), (1, 2)]
+        );
+    }
+
+    #[test]
+    pub fn invalid_schematics() {
+        assert!(Schematic::parse("###\n#.#\n...\n").is_ok());
+        assert!(Schematic::parse("###\n.##\n#..\n...\n").is_err());
+        assert!(Schematic::parse("###\n##\n...\n").is_err());
+        assert!(Schematic::parse("#.#\n...\n").is_err());
+    }
 }
@@ -1,70 +1,208 @@
 use aoc_runner_derive::aoc;
 use strum::IntoEnumIterator;
 
-fn parse(input: &str) -> pathfinding::matrix::Matrix<char> {
-    let mut matrix = pathfinding::matrix::Matrix::new(
-        input.lines().count(),
-        input.lines().next().unwrap().len(),
-        ' ',
-    );
-    input.lines().enumerate().for_each(|(y, line)| {
-        line.chars().enumerate().for_each(|(x, c)| {
-            matrix[(x, y)] = c;
-        });
-    });
-    matrix
-}
-
 #[aoc(day4, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let matrix = parse(input);
-    matrix
-        .items()
-        .filter_map(|((x, y), &tile)| (tile == 'X').then_some((x, y)))
-        .map(|(x, y)| {
-            Direction::iter()
-                .filter(|direction| {
-                    ['M', 'A', 'S']
-                        .into_iter()
-                        .try_fold((x, y), |(x, y), letter| {
-                            direction
-                                .propagate(&matrix, (x, y))
-                                .and_then(|(x, y)| (matrix[(x, y)] == letter).then_some((x, y)))
-                        })
-                        .is_some()
-                })
-                .count()
-        })
-        .sum()
+    WordSearch::parse(input)
+        .find(&Pattern::Word("XMAS".to_owned()))
+        .len()
 }
 
 #[aoc(day4, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    let matrix = parse(input);
-    matrix
-        .items()
-        .filter_map(|((x, y), &tile)| (tile == 'A').then_some((x, y)))
-        .filter(|&(x, y)| {
-            [
-                (Direction::NorthWest, Direction::SouthEast),
-                (Direction::NorthEast, Direction::SouthWest),
-            ]
-            .into_iter()
-            .all(|(dir1, dir2)| {
-                dir1.propagate(&matrix, (x, y)).and_then(|(x1, y1)| {
-                    dir2.propagate(&matrix, (x, y)).map(|(x2, y2)| {
-                        (matrix[(x1, y1)] == 'M' && matrix[(x2, y2)] == 'S')
-                            || (matrix[(x1, y1)] == 'S' && matrix[(x2, y2)] == 'M')
-                    })
-                }) == Some(true)
+    WordSearch::parse(input)
+        .find(&Pattern::Stencil(Stencil::parse("M.S\n.A.\nM.S").unwrap()))
+        .len()
+}
+
+pub enum Pattern {
+    /// Word spelled along any of the 8 directions.
+    Word(String),
+    /// Arbitrary 2D stencil matched in any rotation or reflection.
+    Stencil(Stencil),
+}
+
+/// Set of required characters at offsets relative to the first required character.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stencil(Vec<((isize, isize), char)>);
+
+impl Stencil {
+    /// Parses a stencil from lines of characters, where `.` matches any character.
+    pub fn parse(input: &str) -> Result<Self, &'static str> {
+        let cells = input
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|&(_, c)| c != '.')
+                    .map(move |(x, c)| ((x as isize, y as isize), c))
             })
-        })
-        .count()
+            .collect::<Vec<_>>();
+        let &((x0, y0), _) = cells
+            .first()
+            .ok_or("Stencil must contain at least one required character")?;
+        Ok(Self(
+            cells
+                .into_iter()
+                .map(|((x, y), c)| ((x - x0, y - y0), c))
+                .collect(),
+        ))
+    }
+
+    fn transform(&self, transform: Transform) -> Self {
+        Self(
+            self.0
+                .iter()
+                .map(|&(offset, c)| (transform.apply(offset), c))
+                .collect(),
+        )
+    }
+
+    /// Translation-invariant representation used to detect symmetric orientations.
+    fn normalized(&self) -> Vec<((isize, isize), char)> {
+        let mut cells = self.0.clone();
+        cells.sort_unstable();
+        let (x0, y0) = cells[0].0;
+        cells
+            .into_iter()
+            .map(|((x, y), c)| ((x - x0, y - y0), c))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::EnumIter)]
+pub enum Transform {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+    AntiTranspose,
+}
+
+impl Transform {
+    #[must_use]
+    pub fn apply(self, (x, y): (isize, isize)) -> (isize, isize) {
+        match self {
+            Self::Identity => (x, y),
+            Self::Rotate90 => (-y, x),
+            Self::Rotate180 => (-x, -y),
+            Self::Rotate270 => (y, -x),
+            Self::FlipHorizontal => (-x, y),
+            Self::FlipVertical => (x, -y),
+            Self::Transpose => (y, x),
+            Self::AntiTranspose => (-y, -x),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Direction(Direction),
+    Transform(Transform),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Match {
+    /// Index of the matched pattern.
+    pub pattern: usize,
+    /// Position `(x, y)` of the first character of the pattern.
+    pub position: (usize, usize),
+    pub orientation: Orientation,
 }
 
-#[derive(strum::EnumIter)]
+pub struct WordSearch(pathfinding::matrix::Matrix<char>);
+
+impl WordSearch {
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
+        Self(pathfinding::matrix::Matrix::from_rows(input.lines().map(str::chars)).unwrap())
+    }
+
+    #[must_use]
+    pub fn find(&self, pattern: &Pattern) -> Vec<Match> {
+        self.find_all(std::slice::from_ref(pattern))
+    }
+
+    /// Finds all patterns in all their distinct orientations with a single scan of the grid
+    /// (empty words never match).
+    #[must_use]
+    pub fn find_all(&self, patterns: &[Pattern]) -> Vec<Match> {
+        // Group the oriented stencils by their first character
+        let mut stencils = rustc_hash::FxHashMap::<char, Vec<_>>::default();
+        for (i, pattern) in patterns.iter().enumerate() {
+            let mut seen = Vec::new();
+            for (orientation, stencil) in Self::orientations(pattern) {
+                let Some(&(_, first)) = stencil.0.first() else {
+                    continue;
+                };
+                let normalized = stencil.normalized();
+                if !seen.contains(&normalized) {
+                    seen.push(normalized);
+                    stencils
+                        .entry(first)
+                        .or_default()
+                        .push((i, orientation, stencil));
+                }
+            }
+        }
+
+        let mut matches = Vec::new();
+        for ((y, x), c) in self.0.items() {
+            let Some(candidates) = stencils.get(c) else {
+                continue;
+            };
+            for (pattern, orientation, stencil) in candidates {
+                if stencil.0.iter().all(|&((dx, dy), c)| {
+                    self.get(x.wrapping_add_signed(dx), y.wrapping_add_signed(dy)) == Some(c)
+                }) {
+                    matches.push(Match {
+                        pattern: *pattern,
+                        position: (x, y),
+                        orientation: *orientation,
+                    });
+                }
+            }
+        }
+        matches
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<char> {
+        self.0.get((y, x)).copied()
+    }
+
+    fn orientations(pattern: &Pattern) -> Vec<(Orientation, Stencil)> {
+        match pattern {
+            Pattern::Word(word) => Direction::iter()
+                .map(|direction| {
+                    let (dx, dy) = direction.offset();
+                    let stencil = Stencil(
+                        word.chars()
+                            .enumerate()
+                            .map(|(i, c)| ((i as isize * dx, i as isize * dy), c))
+                            .collect(),
+                    );
+                    (Orientation::Direction(direction), stencil)
+                })
+                .collect(),
+            Pattern::Stencil(stencil) => Transform::iter()
+                .map(|transform| {
+                    (
+                        Orientation::Transform(transform),
+                        stencil.transform(transform),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::EnumIter)]
 pub enum Direction {
     North,
     NorthWest,
@@ -77,48 +215,18 @@ pub enum Direction {
 }
 
 impl Direction {
-    fn propagate(
-        &self,
-        matrix: &pathfinding::matrix::Matrix<char>,
-        (x, y): (usize, usize),
-    ) -> Option<(usize, usize)> {
-        Some(match self {
-            Self::North => (x, y.checked_sub(1)?),
-            Self::NorthWest => (x.checked_sub(1)?, y.checked_sub(1)?),
-            Self::West => (x.checked_sub(1)?, y),
-            Self::SouthWest => (x.checked_sub(1)?, {
-                let y = y + 1;
-                (y < matrix.rows).then_some(y)?
-            }),
-            Self::South => (x, {
-                let y = y + 1;
-                (y < matrix.rows).then_some(y)?
-            }),
-            Self::SouthEast => (
-                {
-                    let x = x + 1;
-                    (x < matrix.columns).then_some(x)?
-                },
-                {
-                    let y = y + 1;
-                    (y < matrix.rows).then_some(y)?
-                },
-            ),
-            Self::East => (
-                {
-                    let x = x + 1;
-                    (x < matrix.columns).then_some(x)?
-                },
-                y,
-            ),
-            Self::NorthEast => (
-                {
-                    let x = x + 1;
-                    (x < matrix.columns).then_some(x)?
-                },
-                y.checked_sub(1)?,
-            ),
-        })
+    #[must_use]
+    pub fn offset(self) -> (isize, isize) {
+        match self {
+            Self::North => (0, -1),
+            Self::NorthWest => (-1, -1),
+            Self::West => (-1, 0),
+            Self::SouthWest => (-1, 1),
+            Self::South => (0, 1),
+            Self::SouthEast => (1, 1),
+            Self::East => (1, 0),
+            Self::NorthEast => (1, -1),
+        }
     }
 }
 
@@ -149,4 +257,56 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 9);
     }
+
+    #[test]
+    pub fn find_example() {
+        let search = WordSearch::parse(indoc! {"
+            ..X...
+            .SAMX.
+            .A..A.
+            XMAS.S
+            .X....
+        "});
+        let matches = search.find(&Pattern::Word("XMAS".to_owned()));
+        assert_eq!(matches.len(), 4);
+        assert!(matches.contains(&Match {
+            pattern: 0,
+            position: (4, 1),
+            orientation: Orientation::Direction(Direction::West),
+        }));
+    }
+
+    #[test]
+    pub fn find_all_example() {
+        let search = WordSearch::parse(SAMPLE);
+        let patterns = [
+            Pattern::Word("XMAS".to_owned()),
+            Pattern::Stencil(Stencil::parse("M.S\n.A.\nM.S").unwrap()),
+            Pattern::Word("SAS".to_owned()),
+            Pattern::Word(String::new()),
+        ];
+        let matches = search.find_all(&patterns);
+        assert_eq!(matches.iter().filter(|x| x.pattern == 0).count(), 18);
+        assert_eq!(matches.iter().filter(|x| x.pattern == 1).count(), 9);
+        // "SAS" reads the same in both directions, so its only occurrence (in the 7th row)
+        // matches once, read westwards from its last character
+        assert_eq!(
+            matches
+                .iter()
+                .filter(|x| x.pattern == 2)
+                .map(|x| x.position)
+                .collect::<Vec<_>>(),
+            [(6, 6)]
+        );
+        assert!(matches.iter().all(|x| x.pattern != 3));
+    }
+
+    #[test]
+    pub fn empty_stencils() {
+        assert!(Stencil::parse("").is_err());
+        assert!(Stencil::parse("...\n...").is_err());
+        assert!(WordSearch::parse(SAMPLE)
+            .find(&Pattern::Word(String::new()))
+            .is_empty());
+    }
 }
@@ -1,20 +1,15 @@
 use aoc_runner_derive::aoc;
 
-fn parse_input(
-    input: &str,
-) -> (
-    rustc_hash::FxHashSet<(u8, u8)>,
-    impl Iterator<Item = smallvec::SmallVec<[u8; 23]>> + '_,
-) {
-    let rules: rustc_hash::FxHashSet<(u8, u8)> = input
+fn parse_input(input: &str) -> (RuleGraph<u32>, impl Iterator<Item = Vec<u32>> + '_) {
+    let (rules, updates) = input.split_once("\n\n").unwrap();
+    let rules = rules
         .lines()
-        .take_while(|line| !line.is_empty())
         .filter_map(|line| {
             line.split_once('|')
                 .map(|(a, b)| (a.parse().unwrap(), b.parse().unwrap()))
         })
         .collect();
-    let updates = input.lines().skip(rules.len() + 1).map(|update| {
+    let updates = updates.lines().map(|update| {
         update
             .split(',')
             .filter_map(|num| num.parse().ok())
@@ -25,35 +20,145 @@ fn parse_input(
 
 #[aoc(day5, part1)]
 #[must_use]
-pub fn part1(input: &str) -> u16 {
+pub fn part1(input: &str) -> u32 {
     let (rules, updates) = parse_input(input);
     updates
-        .filter_map(|update| {
-            update
-                .is_sorted_by(|a, b| !rules.contains(&(*b, *a)))
-                .then_some(u16::from(update[update.len() / 2]))
-        })
+        .filter(|update| rules.is_ordered(update))
+        .map(|update| update[update.len() / 2])
         .sum()
 }
 
 #[aoc(day5, part2)]
 #[must_use]
-pub fn part2(input: &str) -> u16 {
+pub fn part2(input: &str) -> u32 {
     let (rules, updates) = parse_input(input);
     updates
-        .filter_map(|mut update| {
-            (!update.is_sorted_by(|a, b| !rules.contains(&(*b, *a)))).then_some({
-                update.sort_by(|a, b| {
-                    if rules.contains(&(*a, *b)) {
-                        std::cmp::Ordering::Less
-                    } else {
-                        std::cmp::Ordering::Greater
-                    }
-                });
-                u16::from(update[update.len() / 2])
+        .filter(|update| !rules.is_ordered(update))
+        .map(|update| rules.order(&update).unwrap()[update.len() / 2])
+        .sum()
+}
+
+/// Rule `X|Y` that requires page `X` to be printed before page `Y`, violated by an update.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation<T> {
+    pub rule: (T, T),
+    /// Positions of `X` and `Y` in the update (the position of `X` is after `Y`).
+    pub positions: (usize, usize),
+}
+
+/// Directed graph of page ordering rules with arbitrary page identifiers.
+#[derive(Clone, Debug)]
+pub struct RuleGraph<T> {
+    successors: rustc_hash::FxHashMap<T, rustc_hash::FxHashSet<T>>,
+}
+
+impl<T: Clone + Eq + std::hash::Hash> FromIterator<(T, T)> for RuleGraph<T> {
+    fn from_iter<I: IntoIterator<Item = (T, T)>>(rules: I) -> Self {
+        let mut successors = rustc_hash::FxHashMap::<T, rustc_hash::FxHashSet<T>>::default();
+        for (before, after) in rules {
+            successors.entry(before).or_default().insert(after);
+        }
+        Self { successors }
+    }
+}
+
+impl<T: Clone + Eq + std::hash::Hash> RuleGraph<T> {
+    #[must_use]
+    pub fn contains(&self, before: &T, after: &T) -> bool {
+        self.successors
+            .get(before)
+            .is_some_and(|successors| successors.contains(after))
+    }
+
+    #[must_use]
+    pub fn is_ordered(&self, update: &[T]) -> bool {
+        self.violation(update).is_none()
+    }
+
+    /// Returns the first rule violated by the update, if any.
+    #[must_use]
+    pub fn violation(&self, update: &[T]) -> Option<Violation<T>> {
+        update.iter().enumerate().find_map(|(i, a)| {
+            update[i + 1..].iter().enumerate().find_map(|(j, b)| {
+                self.contains(b, a).then(|| Violation {
+                    rule: (b.clone(), a.clone()),
+                    positions: (i + 1 + j, i),
+                })
             })
         })
-        .sum()
+    }
+
+    /// Orders the pages of an update topologically according to the rules restricted to it.
+    ///
+    /// Pages without a mutual rule keep their relative order. If the restricted rules are
+    /// cyclic, one of the cycles is returned as an error instead.
+    pub fn order(&self, update: &[T]) -> Result<Vec<T>, Vec<T>> {
+        let successors = self.restricted(update);
+        let mut in_degree = vec![0_usize; update.len()];
+        successors.iter().flatten().for_each(|&j| in_degree[j] += 1);
+
+        let mut ready = std::collections::BinaryHeap::new();
+        ready.extend(
+            (0..update.len())
+                .filter(|&i| in_degree[i] == 0)
+                .map(std::cmp::Reverse),
+        );
+        let mut order = Vec::with_capacity(update.len());
+        while let Some(std::cmp::Reverse(i)) = ready.pop() {
+            order.push(update[i].clone());
+            for &j in &successors[i] {
+                in_degree[j] -= 1;
+                if in_degree[j] == 0 {
+                    ready.push(std::cmp::Reverse(j));
+                }
+            }
+        }
+
+        if order.len() == update.len() {
+            Ok(order)
+        } else {
+            let stuck = in_degree.iter().map(|&x| x > 0).collect::<Vec<_>>();
+            Err(Self::find_cycle(&successors, &stuck)
+                .into_iter()
+                .map(|i| update[i].clone())
+                .collect())
+        }
+    }
+
+    #[must_use]
+    pub fn is_acyclic(&self, update: &[T]) -> bool {
+        self.order(update).is_ok()
+    }
+
+    /// Adjacency lists of the rules restricted to the pages of the update (by position).
+    fn restricted(&self, update: &[T]) -> Vec<Vec<usize>> {
+        update
+            .iter()
+            .map(|a| {
+                (0..update.len())
+                    .filter(|&j| self.contains(a, &update[j]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn find_cycle(successors: &[Vec<usize>], stuck: &[bool]) -> Vec<usize> {
+        // Each page that could not be ordered has a predecessor that could not be ordered
+        // either, so walking backwards along such predecessors must revisit a page
+        let mut visited = vec![None; successors.len()];
+        let mut path = Vec::new();
+        let mut node = stuck.iter().position(|&x| x).unwrap();
+        while visited[node].is_none() {
+            visited[node] = Some(path.len());
+            path.push(node);
+            node = (0..successors.len())
+                .find(|&i| stuck[i] && successors[i].contains(&node))
+                .unwrap();
+        }
+        let mut cycle = path.split_off(visited[node].unwrap());
+        cycle.reverse();
+        cycle
+    }
 }
 
 #[cfg(test)]
@@ -101,4 +206,30 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 123);
     }
+
+    #[test]
+    pub fn violation_example() {
+        let (rules, _) = parse_input(SAMPLE);
+        assert_eq!(rules.violation(&[75, 47, 61, 53, 29]), None);
+        assert_eq!(
+            rules.violation(&[75, 97, 47, 61, 53]),
+            Some(Violation {
+                rule: (97, 75),
+                positions: (1, 0),
+            })
+        );
+        assert_eq!(
+            rules.order(&[97, 13, 75, 29, 47]),
+            Ok(vec![97, 75, 47, 29, 13])
+        );
+    }
+
+    #[test]
+    pub fn cyclic_rules() {
+        let rules: RuleGraph<&str> = [("a", "b"), ("b", "c"), ("c", "a"), ("x", "a")]
+            .into_iter()
+            .collect();
+        assert!(rules.is_acyclic(&["a", "b", "x"]));
+        assert_eq!(rules.order(&["x", "c", "b", "a"]), Err(vec!["a", "b", "c"]));
+    }
 }
@@ -4,83 +4,117 @@ use itertools::Itertools;
 #[aoc(day8, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let map = Map::parse(input);
-    map.nodes()
-        .iter()
-        .filter(|node| !node.is_empty())
-        .flat_map(|node| {
-            node.iter()
-                .permutations(2)
-                .map(|pair| (pair[0], pair[1]))
-                .filter_map(|(&(x1, y1), &(x2, y2))| {
-                    if let (Some(x), Some(y)) = ((2 * x2).checked_sub(x1), (2 * y2).checked_sub(y1))
-                    {
-                        if x < map.ncols() && y < map.nrows() {
-                            return Some((x, y));
-                        }
-                    }
-                    None
-                })
-        })
-        .collect::<rustc_hash::FxHashSet<_>>()
-        .len()
+    Map::parse(input).count_antinodes(Harmonics::Ratio(2))
 }
 
 #[aoc(day8, part2)]
 #[must_use]
 pub fn part2(input: &str) -> usize {
-    let map = Map::parse(input);
-    let (ncols, nrows) = (map.ncols() as i16, map.nrows() as i16);
-    map.nodes()
-        .iter()
-        .filter(|node| !node.is_empty())
-        .flat_map(|node| {
-            node.iter()
-                .permutations(2)
-                .map(|pair| (pair[0], pair[1]))
-                .flat_map(|(&(x1, y1), &(x2, y2))| {
-                    let (x1, y1, x, y) = (x1 as i16, y1 as i16, x2 as i16, y2 as i16);
-                    let (dx, dy) = ((x - x1), (y - y1));
-                    std::iter::successors(Some((x, y)), move |(x, y)| {
-                        let (x, y) = (x + dx, y + dy);
-                        if x >= 0 && y >= 0 && x < ncols && y < nrows {
-                            return Some((x, y));
-                        }
-                        None
-                    })
-                })
-        })
-        .collect::<rustc_hash::FxHashSet<_>>()
-        .len()
+    Map::parse(input).count_antinodes(Harmonics::Resonant)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Harmonics {
+    /// Points on the line through a pair of antennas where one antenna is `n` times as far
+    /// away as the other (part 1 uses `2`).
+    Ratio(u32),
+    /// Every point on the line through a pair of antennas (part 2).
+    Resonant,
 }
 
-#[repr(transparent)]
-#[derive(Clone, derive_more::Deref, derive_more::DerefMut)]
-struct Map(nalgebra::DMatrix<u8>);
+pub struct Map {
+    width: i64,
+    height: i64,
+    antennas: std::collections::BTreeMap<char, Vec<(i64, i64)>>,
+}
 
 impl Map {
-    fn parse(input: &str) -> Self {
-        let input = input.as_bytes();
-        let width = input.iter().position(|&c| c == b'\n').unwrap();
-        let mut map = nalgebra::DMatrix::from_element(width, input.len() / width, b'.');
-        input.chunks(width + 1).enumerate().for_each(|(y, line)| {
-            line.iter().enumerate().for_each(|(x, c)| match c {
-                b'.' | b'\n' => {}
-                _ => map[(x, y)] = *c,
-            });
-        });
-        Self(map)
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
+        let mut antennas = std::collections::BTreeMap::<_, Vec<_>>::new();
+        for (y, line) in input.lines().enumerate() {
+            for (x, c) in line.chars().enumerate().filter(|&(_, c)| c != '.') {
+                antennas.entry(c).or_default().push((x as i64, y as i64));
+            }
+        }
+        Self {
+            width: input.lines().next().map_or(0, str::len) as i64,
+            height: input.lines().count() as i64,
+            antennas,
+        }
+    }
+
+    #[must_use]
+    pub fn count_antinodes(&self, harmonics: Harmonics) -> usize {
+        self.antinodes(harmonics)
+            .into_values()
+            .flatten()
+            .collect::<rustc_hash::FxHashSet<_>>()
+            .len()
     }
 
-    fn nodes(&self) -> [smallvec::SmallVec<[(usize, usize); 4]>; u8::MAX as usize] {
-        let mut nodes = std::array::from_fn(|_| smallvec::SmallVec::new());
-        (0..self.ncols())
-            .cartesian_product(0..self.nrows())
-            .filter(|&(y, x)| self[(x, y)] != b'.')
-            .for_each(|(y, x)| {
-                nodes[self[(x, y)] as usize].push((x, y));
-            });
-        nodes
+    /// Returns the sorted antinodes within the map for each frequency.
+    #[must_use]
+    pub fn antinodes(
+        &self,
+        harmonics: Harmonics,
+    ) -> std::collections::BTreeMap<char, Vec<(i64, i64)>> {
+        self.antennas
+            .iter()
+            .map(|(&frequency, antennas)| {
+                let antinodes = antennas
+                    .iter()
+                    .tuple_combinations()
+                    .flat_map(|(&a, &b)| self.pair_antinodes(a, b, harmonics))
+                    .sorted_unstable_by_key(|&(x, y)| (y, x))
+                    .dedup()
+                    .collect();
+                (frequency, antinodes)
+            })
+            .collect()
+    }
+
+    fn contains(&self, (x, y): (i64, i64)) -> bool {
+        (0..self.width).contains(&x) && (0..self.height).contains(&y)
+    }
+
+    fn pair_antinodes(
+        &self,
+        a: (i64, i64),
+        b: (i64, i64),
+        harmonics: Harmonics,
+    ) -> Vec<(i64, i64)> {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        match harmonics {
+            Harmonics::Ratio(ratio) => {
+                // Points `a + t * (b - a)` with `|t| = ratio * |t - 1|` (and symmetrically),
+                // i.e. `t = ratio / (ratio - 1)` or `t = ratio / (ratio + 1)`
+                let ratio = i64::from(ratio);
+                [(ratio, ratio - 1), (ratio, ratio + 1)]
+                    .into_iter()
+                    .filter(|&(_, den)| {
+                        den != 0 && (dx * ratio) % den == 0 && (dy * ratio) % den == 0
+                    })
+                    .flat_map(|(num, den)| {
+                        let (sx, sy) = (dx * num / den, dy * num / den);
+                        [(a.0 + sx, a.1 + sy), (b.0 - sx, b.1 - sy)]
+                    })
+                    .filter(|&pos| self.contains(pos))
+                    .dedup()
+                    .collect()
+            }
+            Harmonics::Resonant => {
+                // Reduce the direction so that every grid point on the line is visited
+                let gcd = num_integer::gcd(dx, dy);
+                let (dx, dy) = (dx / gcd, dy / gcd);
+                let forward = std::iter::successors(Some(a), |&(x, y)| Some((x + dx, y + dy)));
+                let backward = std::iter::successors(Some(a), |&(x, y)| Some((x - dx, y - dy)));
+                forward
+                    .take_while(|&pos| self.contains(pos))
+                    .chain(backward.skip(1).take_while(|&pos| self.contains(pos)))
+                    .collect()
+            }
+        }
     }
 }
 
@@ -127,4 +161,50 @@ mod tests {
         assert_eq!(part2(SAMPLE), 34);
         assert_eq!(part2(SAMPLE2), 9);
     }
+
+    #[test]
+    pub fn antinodes_per_frequency() {
+        let antinodes = Map::parse(SAMPLE).antinodes(Harmonics::Ratio(2));
+        assert_eq!(antinodes[&'0'].len(), 10);
+        assert_eq!(antinodes[&'A'].len(), 5);
+        assert_eq!(
+            Map::parse(SAMPLE2).antinodes(Harmonics::Ratio(2))[&'T'],
+            [(5, 0), (6, 2), (2, 4)]
+        );
+    }
+
+    #[test]
+    pub fn last_column() {
+        // The antinode of 'a' right of the last column lies outside the map, while the
+        // antinode of 'b' on the last column lies inside
+        let input = indoc! {"
+            ..aa
+            ....
+            .bb.
+            ....
+        "};
+        let antinodes = Map::parse(input).antinodes(Harmonics::Ratio(2));
+        assert_eq!(antinodes[&'a'], [(1, 0)]);
+        assert_eq!(antinodes[&'b'], [(0, 2), (3, 2)]);
+        assert_eq!(part1(input), 3);
+        assert_eq!(part2(input), 8);
+    }
+
+    #[test]
+    pub fn reduced_direction() {
+        let map = Map::parse(indoc! {"
+            a.........
+            ..........
+            ....a.....
+            ..........
+            ..........
+            ..........
+        "});
+        assert_eq!(
+            map.antinodes(Harmonics::Resonant)[&'a'],
+            [(0, 0), (2, 1), (4, 2), (6, 3), (8, 4)]
+        );
+        assert_eq!(map.antinodes(Harmonics::Ratio(2))[&'a'], [(8, 4)]);
+        assert_eq!(map.antinodes(Harmonics::Ratio(3))[&'a'], [(6, 3)]);
+    }
 }
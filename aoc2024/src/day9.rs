@@ -1,113 +1,183 @@
 use aoc_runner_derive::aoc;
-use itertools::Itertools;
 
 #[aoc(day9, part1)]
 #[must_use]
 pub fn part1(input: &str) -> usize {
-    let mut memory = Vec::with_capacity(51200);
-
-    let input = input.bytes().enumerate();
-    let mut digits_rev = input
-        .clone()
-        .rev()
-        .skip_while(|&(_, b)| !b.is_ascii_digit());
-    let (mut i_rev, mut i_id_rev, mut b_rev, mut b_remaining_rev);
-    (i_rev, b_rev) = digits_rev.next().unwrap();
-    if i_rev % 2 == 1 {
-        (i_rev, b_rev) = digits_rev.next().unwrap();
+    let mut disk = Disk::parse(input);
+    disk.compact(Compaction::BlockWise);
+    disk.checksum()
+}
+
+#[aoc(day9, part2)]
+#[must_use]
+pub fn part2(input: &str) -> usize {
+    let mut disk = Disk::parse(input);
+    disk.compact(Compaction::FirstFit);
+    disk.checksum()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compaction {
+    /// Move individual blocks from the end of the disk into the leftmost free block.
+    BlockWise,
+    /// Move whole files into the leftmost free span that fits them.
+    FirstFit,
+    /// Move whole files into the smallest free span that fits them (leftmost on ties).
+    BestFit,
+}
+
+/// Contiguous run of blocks that belong to a single file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub id: usize,
+    pub start: usize,
+    pub len: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Disk {
+    /// File segments ordered by file ID (a file may be fragmented after block-wise compaction).
+    segments: Vec<Segment>,
+    len: usize,
+}
+
+impl Disk {
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
+        let mut segments = Vec::with_capacity(10000);
+        let len = input
+            .bytes()
+            .filter(u8::is_ascii_digit)
+            .enumerate()
+            .fold(0, |start, (i, b)| {
+                let len = (b - b'0') as usize;
+                if i % 2 == 0 {
+                    segments.push(Segment {
+                        id: i / 2,
+                        start,
+                        len,
+                    });
+                }
+                start + len
+            });
+        Self { segments, len }
     }
-    i_id_rev = i_rev / 2;
-    b_remaining_rev = b_rev - b'0';
 
-    for (i, b) in input {
-        if i >= i_rev {
-            for _ in 0..b_remaining_rev {
-                memory.push(i_id_rev);
-            }
-            break;
+    #[must_use]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn compact(&mut self, strategy: Compaction) {
+        match strategy {
+            Compaction::BlockWise => self.compact_blocks(),
+            Compaction::FirstFit => self.compact_files(false),
+            Compaction::BestFit => self.compact_files(true),
         }
-        if i % 2 == 0 {
-            let i_id = i / 2;
-            for _ in 0..(b - b'0') {
-                memory.push(i_id);
-            }
-        } else {
-            for _ in 0..(b - b'0') {
-                if b_remaining_rev == 0 {
-                    _ = digits_rev.next().unwrap();
-                    (i_rev, b_rev) = digits_rev.next().unwrap();
-                    if i >= i_rev {
-                        break;
-                    }
-                    i_id_rev = i_rev / 2;
-                    b_remaining_rev = b_rev - b'0';
-                }
-                b_remaining_rev -= 1;
-                memory.push(i_id_rev);
+        self.segments
+            .sort_by_key(|segment| (segment.id, segment.start));
+    }
+
+    #[must_use]
+    pub fn checksum(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| {
+                // Sum of the positions `start..start + len`
+                let len = segment.len;
+                segment.id * (len * segment.start + len * len.saturating_sub(1) / 2)
+            })
+            .sum()
+    }
+
+    /// Renders the block map, e.g. `00...111...2...333`, using the last digit of each file ID.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut blocks = vec![b'.'; self.len];
+        for segment in &self.segments {
+            blocks[segment.start..segment.start + segment.len]
+                .fill(b'0' + u8::try_from(segment.id % 10).unwrap());
+        }
+        String::from_utf8(blocks).unwrap()
+    }
+
+    /// Free spans as `(start, len)` ordered by their position.
+    fn free_spans(&self) -> Vec<(usize, usize)> {
+        let mut segments = self.segments.clone();
+        segments.sort_by_key(|segment| segment.start);
+        let mut spans = Vec::with_capacity(segments.len());
+        let end = segments.iter().fold(0, |end, segment| {
+            if segment.start > end {
+                spans.push((end, segment.start - end));
             }
+            segment.start + segment.len
+        });
+        if self.len > end {
+            spans.push((end, self.len - end));
         }
+        spans
     }
-    memory.into_iter().enumerate().map(|(i, id)| i * id).sum()
-}
 
-#[aoc(day9, part2)]
-#[must_use]
-pub fn part2(input: &str) -> usize {
-    let mut files = Vec::with_capacity(10000);
-    input
-        .bytes()
-        .filter(|&b| b.is_ascii_digit())
-        .enumerate()
-        .fold(0, |mut start, (i, b)| {
-            let len = b - b'0';
-            if i % 2 == 0 {
-                files.push(File {
-                    id: i / 2,
+    fn compact_blocks(&mut self) {
+        let mut free = self.free_spans().into_iter();
+        let mut current = free.next();
+        self.segments.sort_by_key(|segment| segment.start);
+        let mut moved = Vec::new();
+        for segment in self.segments.iter_mut().rev() {
+            while segment.len > 0 {
+                let Some((start, len)) = current.filter(|&(start, _)| start < segment.start) else {
+                    break;
+                };
+                let n = len.min(segment.len);
+                moved.push(Segment {
+                    id: segment.id,
                     start,
-                    len,
+                    len: n,
                 });
+                segment.len -= n;
+                current = if n == len {
+                    free.next()
+                } else {
+                    Some((start + n, len - n))
+                };
             }
-            start += len as usize;
-            start
-        });
+        }
+        self.segments.retain(|segment| segment.len > 0);
+        self.segments.extend(moved);
+    }
 
-    (0..=files.last().unwrap().id).rev().for_each(|id| {
-        let file_id = files.iter().position(|file| file.id == id).unwrap();
-        if let Some(new_pos) = files
-            .iter()
-            .tuple_windows()
-            .find_map(|(a, b)| {
-                let end = a.start + a.len as usize;
-                if end > files[file_id].start {
-                    Some(None)
-                } else if b.start - end >= files[file_id].len as usize {
-                    Some(Some(end))
-                } else {
-                    None
-                }
-            })
-            .flatten()
-        {
-            files[file_id].start = new_pos;
+    fn compact_files(&mut self, best_fit: bool) {
+        // Min-heaps of free span starts, indexed by the span size
+        let free_spans = self.free_spans();
+        let max_len = free_spans.iter().map(|&(_, len)| len).max().unwrap_or(0);
+        let mut heaps = vec![std::collections::BinaryHeap::new(); max_len + 1];
+        for (start, len) in free_spans {
+            heaps[len].push(std::cmp::Reverse(start));
         }
-        files.sort_by_key(|file| file.start);
-    });
-
-    files
-        .into_iter()
-        .map(|file| {
-            (file.start..file.start + file.len as usize)
-                .map(|idx| idx * file.id)
-                .sum::<usize>()
-        })
-        .sum()
-}
 
-#[derive(Clone, Copy)]
-struct File {
-    id: usize,
-    start: usize,
-    len: u8,
+        self.segments
+            .sort_by_key(|segment| std::cmp::Reverse((segment.id, segment.start)));
+        for segment in &mut self.segments {
+            let candidates = heaps
+                .iter()
+                .enumerate()
+                .skip(segment.len.max(1))
+                .filter_map(|(len, heap)| heap.peek().map(|&std::cmp::Reverse(start)| (start, len)))
+                .filter(|&(start, _)| start < segment.start);
+            let target = if best_fit {
+                candidates.min_by_key(|&(start, len)| (len, start))
+            } else {
+                candidates.min()
+            };
+            if let Some((start, len)) = target {
+                heaps[len].pop();
+                if len > segment.len {
+                    heaps[len - segment.len].push(std::cmp::Reverse(start + segment.len));
+                }
+                segment.start = start;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -128,4 +198,44 @@ mod tests {
     pub fn part2_example() {
         assert_eq!(part2(SAMPLE), 2858);
     }
+
+    #[test]
+    pub fn block_map() {
+        let mut disk = Disk::parse(SAMPLE);
+        assert_eq!(disk.render(), "00...111...2...333.44.5555.6666.777.888899");
+        disk.compact(Compaction::BlockWise);
+        assert_eq!(disk.render(), "0099811188827773336446555566..............");
+
+        let mut disk = Disk::parse(SAMPLE);
+        disk.compact(Compaction::FirstFit);
+        assert_eq!(disk.render(), "00992111777.44.333....5555.6666.....8888..");
+    }
+
+    #[test]
+    pub fn best_fit() {
+        let mut disk = Disk::parse("1311111");
+        disk.compact(Compaction::FirstFit);
+        assert_eq!(disk.render(), "0321.....");
+
+        let mut disk = Disk::parse("1311111");
+        disk.compact(Compaction::BestFit);
+        assert_eq!(disk.render(), "021..3...");
+    }
+
+    #[test]
+    pub fn empty_files() {
+        let disk = Disk::parse("012");
+        assert_eq!(disk.render(), ".11");
+        assert_eq!(disk.checksum(), 3);
+        for (strategy, blocks, checksum) in [
+            (Compaction::BlockWise, "11.", 1),
+            (Compaction::FirstFit, ".11", 3),
+            (Compaction::BestFit, ".11", 3),
+        ] {
+            let mut disk = disk.clone();
+            disk.compact(strategy);
+            assert_eq!(disk.render(), blocks);
+            assert_eq!(disk.checksum(), checksum);
+        }
+    }
 }
@@ -26,4 +26,8 @@ pub mod day23;
 pub mod day24;
 pub mod day25;
 
+/// Modular arithmetic shared with the 2023 puzzles.
+#[path = "../../aoc2023/src/modular.rs"]
+pub mod modular;
+
 aoc_runner_derive::aoc_lib! { year = 2024 }